@@ -3,13 +3,19 @@ use std::path::PathBuf;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
   napi_build::setup();
 
-  let proto_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/proto/proto");
+  let proto_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("protos");
   let proto_file = proto_dir.join("game.proto");
 
   println!("cargo:rerun-if-changed={}", proto_dir.display());
   println!("cargo:rerun-if-changed={}", proto_file.display());
 
   let mut config = prost_build::Config::new();
+  // The build does not depend on a protoc install
+  config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+  // Map fields iterate in key order, so the same state always encodes to the same bytes
+  config.btree_map(["."]);
+  // Recordings carry the packages still queued for clients when they start
+  config.type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]");
 
   config.compile_protos(&[proto_file], &[proto_dir])?;
 
@@ -2,16 +2,34 @@
 /* eslint-disable */
 export declare class ComputeEngine {
   constructor(props: EngineProps)
+  static fromRecording(bytes: Uint8Array): ComputeEngine
+  appendRecording(bytes: Uint8Array): void
+  seed(): string
   join(playerProps: JoinProps): void
   leave(playerId: number): void
+  ack(id: number, tick: number): void
+  setViewRadius(id: number, radius?: number | undefined | null): void
+  setSendRate(id: number, rate?: number | undefined | null): void
   chatMessage(content: string, id: number): void
   input(id: number, input: Input): void
   onPlayerDeath(callback: (arg: number) => null): void
+  onVictory(callback: (arg: number) => null): void
+  networkStats(): NetworkStats
+  setZstdDictionary(dictionary: Uint8Array): void
+  startRecording(): void
+  exportRecording(): Uint8Array
+  flushRecording(): Uint8Array
+  snapshot(): Uint8Array
+  restore(bytes: Uint8Array): Array<number>
+  replayTick(): object | null
   update(): object
+  updateWithDelta(delta: number): object
+  setClockTime(time: number): void
+  useSystemClock(): void
 }
 
 export declare class EngineProps {
-  constructor(config: string, worlds: Array<string>)
+  constructor(config: string, worlds: Array<string>, threads?: number | undefined | null)
 }
 
 export declare class Input {
@@ -25,6 +43,7 @@ export declare class Input {
   mousePosY: number
   firstAbility: boolean
   secondAbility: boolean
+  sequence: number
   constructor()
   setLeft(val: boolean): void
   setRight(val: boolean): void
@@ -36,15 +55,43 @@ export declare class Input {
   setMousePosY(val: number): void
   setFirstAbility(val: boolean): void
   setSecondAbility(val: boolean): void
+  setSequence(val: number): void
 }
 
 export declare class JoinProps {
   name: string
   id: number
-  constructor(name: string, id: number)
+  hero?: string
+  compression?: string
+  viewRadius?: number
+  constructor(name: string, id: number, hero?: string | undefined | null, compression?: string | undefined | null, viewRadius?: number | undefined | null)
 }
 
 export declare class Vector {
   x: number
   y: number
 }
+
+export interface AbilityInfo {
+  name: string
+  description: string
+  energyCost: number
+  cooldown: number
+}
+
+export declare function getHeroes(): Array<HeroInfo>
+
+export interface HeroInfo {
+  name: string
+  id: number
+  abilities: Array<AbilityInfo>
+}
+
+export declare function trainZstdDictionary(samples: Array<Uint8Array>, maxSize: number): Uint8Array
+
+export interface NetworkStats {
+  encodedBytes: number
+  compressedBytes: number
+  sentBytes: number
+  compressionTime: number
+}
@@ -1,36 +1,82 @@
+use crate::compression::{Codec, Compression};
+use crate::config::Network;
+use crate::managers::interest::ViewState;
 use crate::managers::player::PlayersManager;
 use crate::proto::package::Kind;
-use crate::proto::{Package, Packages};
+use crate::proto::{
+  Package, Packages, PartialEntity, PartialPlayer, UpdateEntitiesMap, UpdatePlayersMap,
+};
 use crate::resources::assets::effect::PlayerEffectWrapper;
 use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::utils::input::Input;
 use crate::resources::utils::vector::Vector;
-use std::collections::HashMap;
+use napi_derive::napi;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct NetworkStats {
+  pub encoded_bytes: i64,
+  pub compressed_bytes: i64,
+  pub sent_bytes: i64,
+  pub compression_time: f64,
+}
 
 pub struct Client {
   pub packages: Packages,
   pub input: Input,
+  pub codec: Codec,
+  pub processed_sequence: u32,
+  pub view_radius: Option<f64>,
+  pub view: ViewState,
+  pub send_interval: f64,
+  pub since_send: f64,
+  pub pending_area: Vec<Package>,
+  pub resync: bool,
 }
 
 pub struct NetworkBus {
   pub direct_clients: HashMap<i64, Client>,
   pub area_clients: HashMap<(String, u64), Packages>,
+  pub stats: NetworkStats,
+  pub compression: Compression,
+  pub codec: Codec,
+  pub acknowledgments: bool,
+  pub view_radius: Option<f64>,
 }
 
 impl NetworkBus {
-  pub fn new() -> Self {
+  pub fn new(network: &Network) -> Self {
     Self {
       direct_clients: HashMap::new(),
       area_clients: HashMap::new(),
+      stats: NetworkStats::default(),
+      compression: Compression::new(network.zstd_level),
+      codec: network.codec,
+      acknowledgments: network.acknowledgments,
+      view_radius: network.view_radius,
     }
   }
 
-  pub fn add_client(&mut self, player_id: i64) {
+  pub fn add_client(&mut self, player_id: i64, codec: Option<Codec>, view_radius: Option<f64>) {
     self.direct_clients.insert(
       player_id,
       Client {
         input: Input::new(),
-        packages: Packages { items: Vec::new() },
+        packages: Packages {
+          items: Vec::new(),
+          tick: 0,
+          baseline: 0,
+        },
+        codec: codec.unwrap_or(self.codec),
+        processed_sequence: 0,
+        view_radius: view_radius.or(self.view_radius),
+        view: ViewState::default(),
+        send_interval: 0.0,
+        since_send: 0.0,
+        pending_area: Vec::new(),
+        resync: false,
       },
     );
   }
@@ -41,6 +87,30 @@ impl NetworkBus {
     }
   }
 
+  pub fn set_view_radius(&mut self, id: i64, view_radius: Option<f64>) {
+    if let Some(client) = self.direct_clients.get_mut(&id) {
+      client.view_radius = view_radius;
+      client.view.reset();
+    }
+  }
+
+  // The client's area is rebuilt from the current state when its next frame goes out
+  pub fn resync(&mut self, id: i64) {
+    if let Some(client) = self.direct_clients.get_mut(&id) {
+      client.resync = true;
+      client.pending_area.clear();
+    }
+  }
+
+  pub fn set_send_rate(&mut self, id: i64, rate: Option<f64>) {
+    if let Some(client) = self.direct_clients.get_mut(&id) {
+      client.send_interval = match rate {
+        Some(rate) if rate > 0.0 => 1000.0 / rate,
+        _ => 0.0,
+      };
+    }
+  }
+
   pub fn accept_input(&mut self, id: i64, input: &Input) {
     if let Some(client) = self.direct_clients.get_mut(&id) {
       client.input = input.clone();
@@ -56,15 +126,18 @@ impl NetworkBus {
   }
 
   pub fn add_area_package(&mut self, name: String, area: u64, package: Kind) {
-    if let Some(area) = self.area_clients.get_mut(&(name.clone(), area)) {
-      area.items.push(Package {
-        kind: Some(package),
-      });
-    } else {
-      self
-        .area_clients
-        .insert((name, area), Packages { items: Vec::new() });
+    // Entity, player and effect state is diffed per client against acknowledged baselines instead
+    if self.acknowledgments {
+      return;
     }
+    let packages = self.area_clients.entry((name, area)).or_insert_with(|| Packages {
+      items: Vec::new(),
+      tick: 0,
+      baseline: 0,
+    });
+    packages.items.push(Package {
+      kind: Some(package),
+    });
   }
 
   pub fn add_direct_package(&mut self, id: i64, package: Kind) {
@@ -74,15 +147,102 @@ impl NetworkBus {
       });
     }
   }
+}
 
+impl Client {
   pub fn clear_packages(&mut self) {
-    for (_, client) in self.direct_clients.iter_mut() {
-      client.packages.items.clear();
+    self.packages.items.clear();
+    self.packages.baseline = 0;
+  }
+}
+
+// Folds the diffs accumulated over several ticks into a single package per kind
+pub fn compact_packages(items: &mut Vec<Package>) {
+  if items.len() < 2 {
+    return;
+  }
+
+  let mut closed_entities = HashSet::new();
+  let mut unstable_players = HashSet::new();
+  let mut last_entities = None;
+  let mut last_players = None;
+  let mut last_roster = None;
+  let mut last_effects = HashMap::new();
+  for (index, package) in items.iter().enumerate() {
+    match &package.kind {
+      Some(Kind::CloseEntities(close)) => closed_entities.extend(close.ids.iter().copied()),
+      Some(Kind::NewPlayer(player)) => {
+        unstable_players.insert(player.id);
+      }
+      Some(Kind::ClosePlayer(id)) => {
+        unstable_players.insert(*id as u32);
+      }
+      Some(Kind::UpdateEntities(_)) => last_entities = Some(index),
+      Some(Kind::UpdatePlayers(_)) => last_players = Some(index),
+      Some(Kind::Roster(_)) => last_roster = Some(index),
+      Some(Kind::UpdateEffect(effect)) => {
+        last_effects.insert((effect.player_id, effect.effect_type, effect.instance), index);
+      }
+      _ => {}
     }
   }
+
+  // Players that left or joined keep their diffs in place, since their order matters
+  let mut entities = BTreeMap::new();
+  let mut players = BTreeMap::new();
+  let mut compacted = Vec::with_capacity(items.len());
+  for (index, mut package) in std::mem::take(items).into_iter().enumerate() {
+    match &mut package.kind {
+      Some(Kind::UpdateEntities(update)) => {
+        for (id, diff) in std::mem::take(&mut update.items) {
+          if !closed_entities.contains(&id) {
+            entities
+              .entry(id)
+              .and_modify(|old: &mut PartialEntity| old.merge(&diff))
+              .or_insert(diff);
+          }
+        }
+        if Some(index) == last_entities && !entities.is_empty() {
+          compacted.push(Package {
+            kind: Some(Kind::UpdateEntities(UpdateEntitiesMap {
+              items: std::mem::take(&mut entities),
+            })),
+          });
+        }
+      }
+      Some(Kind::UpdatePlayers(update)) => {
+        for (id, diff) in std::mem::take(&mut update.items) {
+          if unstable_players.contains(&id) {
+            update.items.insert(id, diff);
+          } else {
+            players
+              .entry(id)
+              .and_modify(|old: &mut PartialPlayer| old.merge(&diff))
+              .or_insert(diff);
+          }
+        }
+        if !update.items.is_empty() {
+          compacted.push(package);
+        }
+        if Some(index) == last_players && !players.is_empty() {
+          compacted.push(Package {
+            kind: Some(Kind::UpdatePlayers(UpdatePlayersMap {
+              items: std::mem::take(&mut players),
+            })),
+          });
+        }
+      }
+      Some(Kind::Roster(_)) if Some(index) != last_roster => {}
+      // Every effect update carries the whole effect, so the latest one is enough
+      Some(Kind::UpdateEffect(effect))
+        if last_effects[&(effect.player_id, effect.effect_type, effect.instance)] != index => {}
+      _ => compacted.push(package),
+    }
+  }
+  *items = compacted;
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PlayerEvent {
   ResPlayerAndMove {
     player_id: i64,
@@ -92,6 +252,7 @@ pub enum PlayerEvent {
     player_id: i64,
     effect_id: u64,
     caster_id: u64,
+    caster_type: u64,
   },
 }
 
@@ -118,7 +279,11 @@ impl EventBus {
       .push(PlayerEvent::ResPlayerAndMove { player_id, pos });
   }
 
-  pub fn process_players_events(&mut self, manager: &mut PlayersManager) {
+  pub fn process_players_events(
+    &mut self,
+    manager: &mut PlayersManager,
+    network_bus: &mut NetworkBus,
+  ) {
     for event in self.players_events.iter() {
       match event {
         PlayerEvent::ResPlayerAndMove { player_id, pos } => {
@@ -131,13 +296,13 @@ impl EventBus {
           effect_id,
           player_id,
           caster_id,
+          caster_type,
         } => {
           if let Some(hero) = manager.players.get(player_id) {
-            // if !manager.has_player_effect(*effect_id, *player_id) {
             if let Ok(effect) = &mut PlayerEffectWrapper::new(*effect_id, hero, *caster_id) {
-              manager.add_player_effect(effect);
+              effect.effect_mut().caster_type = *caster_type;
+              manager.add_player_effect(effect, network_bus);
             }
-            // }
           }
         }
       }
@@ -145,3 +310,127 @@ impl EventBus {
     self.players_events.clear();
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::proto::{CloseEntities, PackedEffect, PackedPlayer, Roster};
+
+  fn package(kind: Kind) -> Package {
+    Package { kind: Some(kind) }
+  }
+
+  fn entity_diff(id: u32, x: Option<i32>, y: Option<i32>) -> Package {
+    let diff = PartialEntity {
+      x,
+      y,
+      ..Default::default()
+    };
+    package(Kind::UpdateEntities(UpdateEntitiesMap {
+      items: BTreeMap::from([(id, diff)]),
+    }))
+  }
+
+  fn player_diff(id: u32, x: i32) -> Package {
+    let diff = PartialPlayer {
+      x: Some(x),
+      ..Default::default()
+    };
+    package(Kind::UpdatePlayers(UpdatePlayersMap {
+      items: BTreeMap::from([(id, diff)]),
+    }))
+  }
+
+  fn effect_update(stacks: u32) -> Package {
+    package(Kind::UpdateEffect(PackedEffect {
+      player_id: 1,
+      effect_type: 2,
+      stacks,
+      ..Default::default()
+    }))
+  }
+
+  fn kinds(items: Vec<Package>) -> Vec<Kind> {
+    items.into_iter().filter_map(|package| package.kind).collect()
+  }
+
+  #[test]
+  fn entity_diffs_fold_into_the_last_one_with_later_fields_winning() {
+    let mut items = vec![
+      entity_diff(1, Some(1), Some(1)),
+      package(Kind::ClosePlayer(9)),
+      entity_diff(1, Some(2), None),
+      entity_diff(2, None, Some(3)),
+    ];
+    compact_packages(&mut items);
+
+    match &kinds(items)[..] {
+      [Kind::ClosePlayer(9), Kind::UpdateEntities(update)] => {
+        assert_eq!((update.items[&1].x, update.items[&1].y), (Some(2), Some(1)));
+        assert_eq!(update.items[&2].y, Some(3));
+      }
+      _ => panic!("expected a single folded entity update after the other packages"),
+    }
+  }
+
+  #[test]
+  fn diffs_of_closed_entities_are_dropped() {
+    let mut items = vec![
+      entity_diff(1, Some(1), None),
+      package(Kind::CloseEntities(CloseEntities { ids: vec![1] })),
+      entity_diff(2, Some(1), None),
+    ];
+    compact_packages(&mut items);
+
+    match &kinds(items)[..] {
+      [Kind::CloseEntities(_), Kind::UpdateEntities(update)] => {
+        assert_eq!(update.items.keys().copied().collect::<Vec<_>>(), vec![2]);
+      }
+      _ => panic!("expected the close and the update of entity 2"),
+    }
+  }
+
+  #[test]
+  fn joining_and_leaving_players_keep_their_diffs_in_place() {
+    let mut items = vec![
+      player_diff(1, 1),
+      player_diff(2, 1),
+      package(Kind::NewPlayer(PackedPlayer {
+        id: 2,
+        ..Default::default()
+      })),
+      player_diff(2, 2),
+      player_diff(1, 2),
+    ];
+    compact_packages(&mut items);
+
+    let kinds = kinds(items);
+    assert_eq!(kinds.len(), 4);
+    assert!(matches!(&kinds[0], Kind::UpdatePlayers(update) if update.items.contains_key(&2)));
+    assert!(matches!(&kinds[1], Kind::NewPlayer(player) if player.id == 2));
+    assert!(matches!(&kinds[2], Kind::UpdatePlayers(update) if update.items[&2].x == Some(2)));
+    match &kinds[3] {
+      Kind::UpdatePlayers(update) => {
+        assert_eq!(update.items.keys().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(update.items[&1].x, Some(2));
+      }
+      _ => panic!("expected the folded update of player 1 last"),
+    }
+  }
+
+  #[test]
+  fn only_the_latest_roster_and_effect_updates_are_kept() {
+    let mut items = vec![
+      package(Kind::Roster(Roster::default())),
+      effect_update(1),
+      package(Kind::Roster(Roster::default())),
+      effect_update(2),
+    ];
+    compact_packages(&mut items);
+
+    match &kinds(items)[..] {
+      [Kind::Roster(_), Kind::UpdateEffect(effect)] => assert_eq!(effect.stacks, 2),
+      _ => panic!("expected one roster and one effect update"),
+    }
+  }
+}
@@ -0,0 +1,93 @@
+use crate::config::Simulation;
+use chrono::Utc;
+
+pub struct Clock {
+  manual_time: Option<i64>,
+  last_timestamp: i64,
+}
+
+impl Clock {
+  pub fn new() -> Self {
+    Self {
+      manual_time: None,
+      last_timestamp: Utc::now().timestamp_millis(),
+    }
+  }
+
+  pub fn now(&self) -> i64 {
+    match self.manual_time {
+      Some(time) => time,
+      None => Utc::now().timestamp_millis(),
+    }
+  }
+
+  pub fn set_time(&mut self, time: i64) {
+    if self.manual_time.is_none() {
+      self.last_timestamp = time;
+    }
+    self.manual_time = Some(time);
+  }
+
+  pub fn use_system_time(&mut self) {
+    self.manual_time = None;
+    self.last_timestamp = Utc::now().timestamp_millis();
+  }
+
+  pub fn delta(&mut self) -> f64 {
+    let time = self.now();
+    let delta = time - self.last_timestamp;
+    self.last_timestamp = time;
+    delta.max(0) as f64
+  }
+}
+
+// Consumes one interval of accumulated time if it is due, tolerating float drift
+pub fn consume_interval(accumulated: &mut f64, interval: f64) -> bool {
+  if interval <= 0.0 {
+    *accumulated = 0.0;
+    return true;
+  }
+  if *accumulated + 1e-6 < interval {
+    return false;
+  }
+  *accumulated = (*accumulated - interval).clamp(0.0, interval);
+  true
+}
+
+pub struct Timestep {
+  pub fixed: bool,
+  pub step: f64,
+  pub max_steps: u32,
+  pub accumulator: f64,
+}
+
+impl Timestep {
+  pub fn new(simulation: &Simulation) -> Self {
+    Self {
+      fixed: simulation.fixed_timestep,
+      step: 1000.0 / simulation.tick_rate.max(1.0),
+      max_steps: simulation.max_steps.max(1),
+      accumulator: 0.0,
+    }
+  }
+
+  // Returns how many steps should be simulated and the delta of each step
+  pub fn advance(&mut self, delta: f64) -> (u32, f64) {
+    if !self.fixed {
+      return (1, delta);
+    }
+
+    self.accumulator += delta;
+    let mut steps = 0;
+    while self.accumulator >= self.step && steps < self.max_steps {
+      self.accumulator -= self.step;
+      steps += 1;
+    }
+
+    if self.accumulator >= self.step {
+      self.accumulator %= self.step;
+    }
+
+    (steps, self.step)
+  }
+}
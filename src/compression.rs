@@ -0,0 +1,120 @@
+use crate::bus::NetworkStats;
+use lz4_flex::frame::FrameEncoder;
+use napi::bindgen_prelude::Uint8Array;
+use napi::{Error, Status};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+  None,
+  Lz4Block,
+  #[default]
+  Lz4Frame,
+  Zstd,
+}
+
+impl Codec {
+  pub fn parse(name: &str) -> Result<Self, Error> {
+    match name {
+      "none" => Ok(Codec::None),
+      "lz4_block" => Ok(Codec::Lz4Block),
+      "lz4_frame" => Ok(Codec::Lz4Frame),
+      "zstd" => Ok(Codec::Zstd),
+      _ => Err(Error::new(
+        Status::InvalidArg,
+        "Unknown compression codec: ".to_string() + name,
+      )),
+    }
+  }
+
+  // Whether two compressed payloads can be sent back to back and decoded as one
+  pub fn concatenable(&self) -> bool {
+    !matches!(self, Codec::Lz4Block)
+  }
+}
+
+pub struct Compression {
+  zstd_level: i32,
+  dictionary: Option<Vec<u8>>,
+  zstd: Option<zstd::bulk::Compressor<'static>>,
+}
+
+impl Compression {
+  pub fn new(zstd_level: i32) -> Self {
+    Self {
+      zstd_level,
+      dictionary: None,
+      zstd: None,
+    }
+  }
+
+  pub fn set_dictionary(&mut self, dictionary: Vec<u8>) {
+    self.dictionary = Some(dictionary);
+    self.zstd = None;
+  }
+
+  pub fn dictionary(&self) -> Option<&Vec<u8>> {
+    self.dictionary.as_ref()
+  }
+
+  pub fn compress(
+    &mut self,
+    codec: Codec,
+    data: &[u8],
+    stats: &mut NetworkStats,
+  ) -> Result<Vec<u8>, Error> {
+    let started = Instant::now();
+    let buffer = match codec {
+      Codec::None => data.to_vec(),
+      Codec::Lz4Block => lz4_flex::block::compress_prepend_size(data),
+      Codec::Lz4Frame => {
+        let mut slice = data;
+        let mut compressor = FrameEncoder::new(Vec::new());
+        io::copy(&mut slice, &mut compressor)?;
+        compressor.finish().map_err(|e| compress_error(e.to_string()))?
+      }
+      Codec::Zstd => self
+        .zstd_compressor()?
+        .compress(data)
+        .map_err(|e| compress_error(e.to_string()))?,
+    };
+    stats.encoded_bytes += data.len() as i64;
+    stats.compressed_bytes += buffer.len() as i64;
+    stats.compression_time += started.elapsed().as_secs_f64() * 1000.0;
+    Ok(buffer)
+  }
+
+  fn zstd_compressor(&mut self) -> Result<&mut zstd::bulk::Compressor<'static>, Error> {
+    if self.zstd.is_none() {
+      let compressor = match &self.dictionary {
+        Some(dictionary) => zstd::bulk::Compressor::with_dictionary(self.zstd_level, dictionary),
+        None => zstd::bulk::Compressor::new(self.zstd_level),
+      };
+      self.zstd = Some(compressor.map_err(|e| compress_error(e.to_string()))?);
+    }
+    Ok(self.zstd.as_mut().unwrap())
+  }
+}
+
+#[napi]
+pub fn train_zstd_dictionary(samples: Vec<Uint8Array>, max_size: u32) -> Result<Uint8Array, Error> {
+  let samples: Vec<&[u8]> = samples.iter().map(|sample| sample.as_ref()).collect();
+  match zstd::dict::from_samples(&samples, max_size as usize) {
+    Ok(dictionary) => Ok(Uint8Array::new(dictionary)),
+    Err(e) => Err(Error::new(
+      Status::InvalidArg,
+      "Attempt to train dictionary ".to_string() + e.to_string().as_str(),
+    )),
+  }
+}
+
+fn compress_error(reason: String) -> Error {
+  Error::new(
+    Status::GenericFailure,
+    "Attempt to compress packages ".to_string() + reason.as_str(),
+  )
+}
@@ -1,9 +1,18 @@
+use crate::compression::Codec;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
   pub spawn: Spawn,
   pub worlds: Vec<String>,
+  #[serde(default)]
+  pub simulation: Simulation,
+  #[serde(default)]
+  pub seed: Option<u64>,
+  #[serde(default)]
+  pub network: Network,
+  #[serde(default)]
+  pub effects: Effects,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -25,13 +34,108 @@ pub struct Spawn {
   pub died_timer: f64,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Simulation {
+  pub fixed_timestep: bool,
+  pub tick_rate: f64,
+  pub max_steps: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Network {
+  #[serde(default)]
+  pub shared_area_frames: bool,
+  #[serde(default)]
+  pub codec: Codec,
+  #[serde(default)]
+  pub zstd_level: i32,
+  #[serde(default)]
+  pub acknowledgments: bool,
+  #[serde(default = "default_baseline_window")]
+  pub baseline_window: usize,
+  #[serde(default)]
+  pub view_radius: Option<f64>,
+  #[serde(default = "default_roster_interval")]
+  pub roster_interval: u64,
+  #[serde(default)]
+  pub send_rate: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Effects {
+  #[serde(default)]
+  pub frozen: Frozen,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frozen {
+  #[serde(default = "default_frozen_duration")]
+  pub duration: f64,
+  // 0 stops the player completely
+  #[serde(default)]
+  pub speed_multiplier: f64,
+  #[serde(default = "default_frozen_abilities")]
+  pub abilities: bool,
+}
+
+fn default_frozen_duration() -> f64 {
+  1500.0
+}
+
+fn default_frozen_abilities() -> bool {
+  true
+}
+
+fn default_baseline_window() -> usize {
+  64
+}
+
+fn default_roster_interval() -> u64 {
+  30
+}
+
+impl Default for Network {
+  fn default() -> Self {
+    Self {
+      shared_area_frames: false,
+      codec: Codec::default(),
+      zstd_level: 0,
+      acknowledgments: false,
+      baseline_window: default_baseline_window(),
+      view_radius: None,
+      roster_interval: default_roster_interval(),
+      send_rate: None,
+    }
+  }
+}
+
+impl Default for Frozen {
+  fn default() -> Self {
+    Self {
+      duration: default_frozen_duration(),
+      speed_multiplier: 0.0,
+      abilities: default_frozen_abilities(),
+    }
+  }
+}
+
+impl Default for Simulation {
+  fn default() -> Self {
+    Self {
+      fixed_timestep: false,
+      tick_rate: 60.0,
+      max_steps: 5,
+    }
+  }
+}
+
 impl Config {
   pub fn new() -> Self {
     Self {
       spawn: Spawn {
         radius: 15.0,
         speed: 17.0,
-        max_speed: 17.0,
+        max_speed: 30.0,
         regeneration: 7.0,
         energy: 30.0,
         max_energy: 30.0,
@@ -44,6 +148,10 @@ impl Config {
         died_timer: 60.0,
       },
       worlds: Vec::new(),
+      simulation: Simulation::default(),
+      seed: None,
+      network: Network::default(),
+      effects: Effects::default(),
     }
   }
 }
@@ -59,6 +167,64 @@ pub struct RawArea {
   pub enemies: Vec<RawEntity>,
   pub w: f64,
   pub h: f64,
+  // Replaces the default portals when not empty
+  #[serde(default)]
+  pub portals: Vec<RawPortal>,
+  #[serde(default)]
+  pub regions: Vec<RawRegion>,
+  #[serde(default)]
+  pub obstacles: Vec<RawObstacle>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum RawObstacle {
+  Rect { x: f64, y: f64, w: f64, h: f64 },
+  Polygon { points: Vec<[f64; 2]> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionKind {
+  Safe,
+  Victory,
+  Spawn,
+  NoAbility,
+}
+
+impl RegionKind {
+  pub fn flag(&self) -> u32 {
+    match self {
+      RegionKind::Safe => 1,
+      RegionKind::Victory => 2,
+      RegionKind::Spawn => 4,
+      RegionKind::NoAbility => 8,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawRegion {
+  pub kind: RegionKind,
+  pub x: f64,
+  pub y: f64,
+  pub w: f64,
+  pub h: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RawPortal {
+  pub x: f64,
+  pub y: f64,
+  pub w: f64,
+  pub h: f64,
+  #[serde(default)]
+  pub world: Option<String>,
+  pub area: u64,
+  #[serde(default)]
+  pub spawn_x: Option<f64>,
+  #[serde(default)]
+  pub spawn_y: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
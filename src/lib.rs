@@ -1,24 +1,32 @@
 // #[deny(clippy::all)]
-use crate::bus::{EventBus, NetworkBus};
-use crate::config::Config;
+use crate::bus::{compact_packages, EventBus, NetworkBus, NetworkStats};
+use crate::clock::{consume_interval, Clock, Timestep};
+use crate::compression::Codec;
+use crate::config::{Config, Network};
+use crate::managers::baseline::BaselinesManager;
+use crate::managers::interest::View;
 use crate::managers::player::PlayersManager;
 use crate::managers::world::WorldsManager;
 use crate::props::EngineProps;
+use crate::recorder::record::Kind as RecordKind;
+use crate::recorder::{
+  RecordedAck, RecordedChat, RecordedClient, RecordedInput, RecordedJoin, RecordedSendRate,
+  RecordedUpdate, RecordedViewRadius, Recorder, Recording, Replay,
+};
 use crate::resources::utils::input::Input;
 use crate::resources::utils::join::JoinProps;
-use crate::resources::UpdateProps;
-use chrono::Utc;
+use crate::resources::{seed_random, UpdateProps};
+use crate::snapshot::{resync_clients, Snapshot};
 use lazy_static::lazy_static;
-use lz4_flex::frame::FrameEncoder;
 use napi::bindgen_prelude::Function;
 use napi::bindgen_prelude::Null;
-use napi::bindgen_prelude::{JsObjectValue, Object, Uint8ArraySlice};
-use napi::{Env, Error};
+use napi::bindgen_prelude::{JsObjectValue, Object, Uint8Array, Uint8ArraySlice};
+use napi::{Env, Error, Status};
 use napi_derive::napi;
-use std::collections::HashMap;
-use std::io;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
-use crate::proto::{Chat, Package, Role};
+use crate::proto::{Chat, Package, Packages, PackedEntity, PackedPlayer, Reconciliation, Role};
 use crate::proto::package::Kind;
 
 pub mod proto {
@@ -26,10 +34,15 @@ pub mod proto {
 }
 
 mod bus;
+mod clock;
+mod compression;
 mod config;
 mod managers;
+mod persist;
 mod props;
+mod recorder;
 mod resources;
+mod snapshot;
 
 lazy_static! {
   pub static ref CONFIG: Mutex<Config> = Mutex::new(Config::new());
@@ -40,62 +53,198 @@ pub struct ComputeEngine {
   players_manager: PlayersManager,
   worlds_manager: WorldsManager,
   network_bus: NetworkBus,
+  baselines: BaselinesManager,
   event_bus: EventBus,
   proto_buffer: Vec<u8>,
 
-  last_timestamp: i64,
+  clock: Clock,
+  timestep: Timestep,
+  network: Network,
+  seed: u64,
+  tick: u64,
+  send_elapsed: f64,
+  raw_config: String,
+  raw_worlds: Vec<String>,
+  recorder: Option<Recorder>,
+  replay: Option<Replay>,
+  remove_dead_players: bool,
   player_death_callback: Option<Function<'static, i64, Null>>,
+  victory_callback: Option<Function<'static, i64, Null>>,
 }
 
 #[napi]
 impl ComputeEngine {
   #[napi(constructor)]
   pub fn new(props: &EngineProps) -> Result<Self, Error> {
+    Self::create(props, None)
+  }
+
+  #[napi(factory)]
+  pub fn from_recording(bytes: Uint8Array) -> Result<Self, Error> {
+    let recording = Recording::import(&bytes)?;
+    let props = EngineProps::new(recording.config.clone(), recording.worlds.clone(), None);
+    let mut engine = Self::create(&props, Some(recording.seed))?;
+    engine.remove_dead_players = recording.remove_dead_players;
+    if let Some(dictionary) = recording.dictionary {
+      engine.network_bus.compression.set_dictionary(dictionary);
+    }
+    // The replay starts from the exact state the recording started in, clients included
+    if let Some(bytes) = &recording.snapshot {
+      let snapshot = Snapshot::decode(bytes)?;
+      engine.tick = snapshot.tick;
+      snapshot.apply(
+        &mut engine.worlds_manager.worlds,
+        &mut engine.players_manager,
+        &mut engine.event_bus,
+      )?;
+    }
+    for recorded in recording.clients.iter() {
+      engine.network_bus.add_client(recorded.id, None, None);
+      if let Some(client) = engine.network_bus.direct_clients.get_mut(&recorded.id) {
+        recorded.apply(client);
+      }
+    }
+    for (key, packages) in recording.area_packages {
+      engine.network_bus.area_clients.insert(key, packages);
+    }
+    engine.baselines = recording.baselines;
+    engine.timestep.accumulator = recording.accumulator;
+    engine.send_elapsed = recording.send_elapsed;
+    engine.replay = Some(Replay::new(recording.records));
+    Ok(engine)
+  }
+
+  #[napi]
+  pub fn append_recording(&mut self, bytes: Uint8Array) -> Result<(), Error> {
+    let records = Recording::import_chunk(&bytes)?;
+    match &mut self.replay {
+      Some(replay) => {
+        replay.extend(records);
+        Ok(())
+      }
+      None => Err(Error::new(
+        Status::GenericFailure,
+        "Engine was not created from a recording".to_string(),
+      )),
+    }
+  }
+
+  fn create(props: &EngineProps, seed: Option<u64>) -> Result<Self, Error> {
     // let worlds = props.load_worlds()?;
     let config = props.load_config()?;
+    let seed = seed.or(config.seed).unwrap_or_else(rand::random);
 
     *CONFIG.lock().unwrap() = config.clone();
+    seed_random(seed);
 
     Ok(Self {
       players_manager: PlayersManager::new(),
-      worlds_manager: WorldsManager::new(props),
-      network_bus: NetworkBus::new(),
-      last_timestamp: Utc::now().timestamp_millis(),
+      worlds_manager: WorldsManager::new(props, seed, &config)?,
+      network_bus: NetworkBus::new(&config.network),
+      baselines: BaselinesManager::new(config.network.baseline_window),
+      clock: Clock::new(),
+      timestep: Timestep::new(&config.simulation),
+      network: config.network.clone(),
+      seed,
+      tick: 0,
+      send_elapsed: 0.0,
+      raw_config: props.raw_config().clone(),
+      raw_worlds: props.raw_worlds().clone(),
+      recorder: None,
+      replay: None,
+      remove_dead_players: false,
       proto_buffer: Vec::with_capacity(1024),
       event_bus: EventBus::new(),
       player_death_callback: None,
+      victory_callback: None,
     })
   }
 
+  #[napi]
+  pub fn seed(&self) -> String {
+    self.seed.to_string()
+  }
+
   #[napi]
   pub fn join(&mut self, player_props: &JoinProps) -> Result<(), Error> {
-    self.network_bus.add_client(player_props.id);
-    self.players_manager.join(
+    let codec = match &player_props.compression {
+      Some(name) => Some(Codec::parse(name)?),
+      None => None,
+    };
+    self.network_bus.add_client(player_props.id, codec, player_props.view_radius);
+    if let Err(e) = self.players_manager.join(
       player_props,
       &mut self.worlds_manager.worlds,
       &mut self.network_bus,
-    )?;
+    ) {
+      self.network_bus.remove_client(player_props.id);
+      return Err(e);
+    }
+    // Only accepted joins are recorded, a rejected one never changed the engine
+    self.record(RecordKind::Join(RecordedJoin {
+      id: player_props.id,
+      name: player_props.name.clone(),
+      hero: player_props.hero.clone(),
+      compression: player_props.compression.clone(),
+      view_radius: player_props.view_radius,
+    }));
     Ok(())
   }
 
   #[napi]
   pub fn leave(&mut self, player_id: i64) {
+    self.record(RecordKind::Leave(player_id));
+    self.remove_player(player_id);
+  }
+
+  fn remove_player(&mut self, player_id: i64) {
     self.players_manager.leave(
       player_id,
       &mut self.worlds_manager.worlds,
       &mut self.network_bus,
     );
     self.network_bus.remove_client(player_id);
+    self.baselines.remove_client(player_id);
+  }
+
+  #[napi]
+  pub fn ack(&mut self, id: i64, tick: i64) {
+    if tick < 0 {
+      return;
+    }
+    self.record(RecordKind::Ack(RecordedAck {
+      id,
+      tick: tick as u64,
+    }));
+    self.baselines.ack(id, tick as u64);
+  }
+
+  #[napi]
+  pub fn set_send_rate(&mut self, id: i64, rate: Option<f64>) {
+    self.record(RecordKind::SendRate(RecordedSendRate { id, rate }));
+    self.network_bus.set_send_rate(id, rate);
+  }
+
+  #[napi]
+  pub fn set_view_radius(&mut self, id: i64, radius: Option<f64>) {
+    self.record(RecordKind::ViewRadius(RecordedViewRadius { id, radius }));
+    self.network_bus.set_view_radius(id, radius);
+    // What the client knows was culled with the old radius
+    self.network_bus.resync(id);
   }
 
   #[napi]
   pub fn chat_message(&mut self, content: String, id: u32) {
+    self.record(RecordKind::Chat(RecordedChat {
+      id,
+      content: content.clone(),
+    }));
     if let Some(hero) = self.players_manager.get_player(id as i64) {
       self.network_bus.add_global_package(Kind::ChatMessage(Chat {
         id,
         content,
         author:hero.player().name.clone(),
-        role: 0,
+        role: Role::Player as i32,
         world: hero.player().world.clone(),
       }))
     }
@@ -103,22 +252,236 @@ impl ComputeEngine {
 
   #[napi]
   pub fn input(&mut self, id: i64, input: &Input) {
+    self.record(RecordKind::Input(RecordedInput::new(id, input)));
     self.network_bus.accept_input(id, input);
   }
 
   #[napi]
   pub fn on_player_death(&mut self, callback: Function<'static, i64, Null>) {
     self.player_death_callback = Some(callback);
+    if !self.remove_dead_players {
+      self.remove_dead_players = true;
+      self.record(RecordKind::PlayerDeathHandler(true));
+    }
+  }
+
+  #[napi]
+  pub fn on_victory(&mut self, callback: Function<'static, i64, Null>) {
+    self.victory_callback = Some(callback);
+  }
+
+  #[napi]
+  pub fn network_stats(&mut self) -> NetworkStats {
+    std::mem::take(&mut self.network_bus.stats)
+  }
+
+  #[napi]
+  pub fn set_zstd_dictionary(&mut self, dictionary: Uint8Array) {
+    self.record(RecordKind::Dictionary(dictionary.to_vec()));
+    self.network_bus.compression.set_dictionary(dictionary.to_vec());
+  }
+
+  #[napi]
+  pub fn start_recording(&mut self) -> Result<(), Error> {
+    // A running session is captured as is, the replay starts from that state
+    let snapshot = if self.tick > 0 || !self.players_manager.players.is_empty() {
+      Some(self.capture().encode()?)
+    } else {
+      None
+    };
+    let clients = self
+      .network_bus
+      .direct_clients
+      .iter()
+      .map(|(id, client)| RecordedClient::new(*id, client))
+      .collect();
+    let mut area_packages: Vec<_> = self
+      .network_bus
+      .area_clients
+      .iter()
+      .map(|(key, packages)| (key.clone(), packages.clone()))
+      .collect();
+    area_packages.sort_by(|a, b| a.0.cmp(&b.0));
+    self.recorder = Some(Recorder::new(Recording {
+      seed: self.seed,
+      config: self.raw_config.clone(),
+      worlds: self.raw_worlds.clone(),
+      remove_dead_players: self.remove_dead_players,
+      snapshot,
+      dictionary: self.network_bus.compression.dictionary().cloned(),
+      clients,
+      area_packages,
+      baselines: self.baselines.clone(),
+      accumulator: self.timestep.accumulator,
+      send_elapsed: self.send_elapsed,
+      records: Vec::new(),
+    }));
+    Ok(())
+  }
+
+  #[napi]
+  pub fn export_recording(&self) -> Result<Uint8Array, Error> {
+    match &self.recorder {
+      Some(recorder) => Ok(Uint8Array::new(recorder.export()?)),
+      None => Err(Error::new(
+        Status::GenericFailure,
+        "Recording was not started".to_string(),
+      )),
+    }
+  }
+
+  // Bounds the recording buffer, chunks after the first are appended to the replay in order
+  #[napi]
+  pub fn flush_recording(&mut self) -> Result<Uint8Array, Error> {
+    match &mut self.recorder {
+      Some(recorder) => Ok(Uint8Array::new(recorder.flush()?)),
+      None => Err(Error::new(
+        Status::GenericFailure,
+        "Recording was not started".to_string(),
+      )),
+    }
+  }
+
+  #[napi]
+  pub fn snapshot(&self) -> Result<Uint8Array, Error> {
+    Ok(Uint8Array::new(self.capture().encode()?))
+  }
+
+  // Returns the ids of connected clients that have no player in the snapshot, they no longer
+  // receive packages and should be disconnected
+  #[napi]
+  pub fn restore(&mut self, bytes: Uint8Array) -> Result<Vec<i64>, Error> {
+    self.record(RecordKind::Restore(bytes.to_vec()));
+    self.restore_snapshot(&bytes)
+  }
+
+  fn capture(&self) -> Snapshot {
+    Snapshot::capture(
+      self.seed,
+      self.tick,
+      &self.worlds_manager.worlds,
+      &self.players_manager,
+      &self.event_bus,
+    )
+  }
+
+  fn restore_snapshot(&mut self, bytes: &[u8]) -> Result<Vec<i64>, Error> {
+    let snapshot = Snapshot::decode(bytes)?;
+    let (seed, tick) = (snapshot.seed, snapshot.tick);
+    snapshot.apply(
+      &mut self.worlds_manager.worlds,
+      &mut self.players_manager,
+      &mut self.event_bus,
+    )?;
+    let dropped = resync_clients(&self.players_manager, &mut self.network_bus);
+    self.seed = seed;
+    self.tick = tick;
+    self.baselines.clear();
+    Ok(dropped)
+  }
+
+  #[napi]
+  pub fn replay_tick(&mut self, env: &Env) -> Result<Option<Object<'_>>, Error> {
+    match self.replay_step()? {
+      Some(delta) => Ok(Some(self.emit(env, delta)?)),
+      None => Ok(None),
+    }
+  }
+
+  // Applies the recorded calls up to and including the next update, whose delta is returned
+  fn replay_step(&mut self) -> Result<Option<f64>, Error> {
+    if self.replay.is_none() {
+      return Err(Error::new(
+        Status::GenericFailure,
+        "Engine was not created from a recording".to_string(),
+      ));
+    }
+
+    while let Some(kind) = self.replay.as_mut().and_then(|replay| replay.next_record()) {
+      match kind {
+        RecordKind::Update(update) => {
+          self.advance(update.delta);
+          return Ok(Some(update.delta));
+        }
+        RecordKind::Input(input) => self.input(input.id, &input.to_input()),
+        RecordKind::Join(join) => {
+          self.join(&JoinProps::new(
+            join.name,
+            join.id,
+            join.hero,
+            join.compression,
+            join.view_radius,
+          ))?
+        }
+        RecordKind::Leave(id) => self.leave(id),
+        RecordKind::Chat(chat) => self.chat_message(chat.content, chat.id),
+        RecordKind::PlayerDeathHandler(enabled) => self.remove_dead_players = enabled,
+        RecordKind::Ack(ack) => self.ack(ack.id, ack.tick as i64),
+        RecordKind::ViewRadius(view) => self.set_view_radius(view.id, view.radius),
+        RecordKind::SendRate(send) => self.set_send_rate(send.id, send.rate),
+        RecordKind::Restore(bytes) => {
+          self.restore(Uint8Array::new(bytes))?;
+        }
+        RecordKind::Dictionary(bytes) => self.set_zstd_dictionary(Uint8Array::new(bytes)),
+      }
+    }
+
+    Ok(None)
+  }
+
+  fn record(&mut self, kind: RecordKind) {
+    if let Some(recorder) = &mut self.recorder {
+      recorder.record(self.tick, kind);
+    }
   }
 
   #[napi]
   pub fn update(&mut self, env: &Env) -> Result<Object<'_>, Error> {
-    let config = CONFIG.lock().unwrap();
+    let delta = self.clock.delta();
+    self.advance(delta);
+    self.emit(env, delta)
+  }
 
-    let time = Utc::now().timestamp_millis();
-    let delta = time - self.last_timestamp;
-    self.last_timestamp = time;
-    let time_fix = delta as f64 / (1000.0 / 30.0);
+  #[napi]
+  pub fn update_with_delta(&mut self, env: &Env, delta: f64) -> Result<Object<'_>, Error> {
+    if !delta.is_finite() {
+      return Err(Error::new(
+        Status::InvalidArg,
+        "Attempt to update with a non-finite delta ".to_string() + delta.to_string().as_str(),
+      ));
+    }
+    // Same as the engine clock, time never runs backwards
+    let delta = delta.max(0.0);
+    self.advance(delta);
+    self.emit(env, delta)
+  }
+
+  #[napi]
+  pub fn set_clock_time(&mut self, time: i64) {
+    self.clock.set_time(time);
+  }
+
+  #[napi]
+  pub fn use_system_clock(&mut self) {
+    self.clock.use_system_time();
+  }
+
+  fn advance(&mut self, delta: f64) {
+    self.record(RecordKind::Update(RecordedUpdate { delta }));
+    self.tick += 1;
+    let (steps, step_delta) = self.timestep.advance(delta);
+    for _ in 0..steps {
+      self.simulate(step_delta);
+    }
+
+    if self.tick.is_multiple_of(self.network.roster_interval.max(1)) {
+      let roster = Kind::Roster(self.players_manager.roster());
+      self.network_bus.add_global_package(roster);
+    }
+  }
+
+  fn simulate(&mut self, delta: f64) {
+    let time_fix = delta / (1000.0 / 30.0);
 
     let update_props = UpdateProps { delta, time_fix };
 
@@ -131,7 +494,7 @@ impl ComputeEngine {
     );
     self
       .worlds_manager
-      .process_warps(&mut self.players_manager, &config, &mut self.network_bus);
+      .process_warps(&mut self.players_manager, &mut self.network_bus);
     self.players_manager.update_behavior(
       &update_props,
       &mut self.worlds_manager.worlds,
@@ -140,62 +503,378 @@ impl ComputeEngine {
     );
     self.players_manager.snapshot_end(&mut self.network_bus);
 
-    if let Some(callback) = self.player_death_callback {
+    if self.remove_dead_players {
       for id in self.players_manager.check_players_to_remove() {
-        self.leave(id as i64);
-        let _ = callback.call(id as i64);
+        self.remove_player(id as i64);
+        if let Some(callback) = self.player_death_callback {
+          let _ = callback.call(id as i64);
+        }
       }
     }
 
-    self.packages_as_napi(env)
+    for id in self.players_manager.victories.drain(..) {
+      if let Some(callback) = self.victory_callback {
+        let _ = callback.call(id);
+      }
+    }
   }
 
-  fn packages_as_napi(&mut self, env: &Env) -> Result<Object<'_>, Error> {
+  fn emit(&mut self, env: &Env, delta: f64) -> Result<Object<'_>, Error> {
     let mut object = Object::new(env)?;
+    for (id, buffer) in self.frames(delta)? {
+      let key = env.create_string(id.to_string())?;
+      let uint8 = Uint8ArraySlice::from_data(env, buffer)?;
+      object.set_property(key, uint8)?;
+    }
+    Ok(object)
+  }
+
+  // Packages keep accumulating in the buses until the send rate lets a frame out
+  fn frames(&mut self, delta: f64) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+    let interval = match self.network.send_rate {
+      Some(rate) if rate > 0.0 => 1000.0 / rate,
+      _ => 0.0,
+    };
+    let accumulated = self.send_elapsed + delta;
+    self.send_elapsed = accumulated;
+    if !consume_interval(&mut self.send_elapsed, interval) {
+      return Ok(Vec::new());
+    }
+    self.build_frames(accumulated - self.send_elapsed)
+  }
+
+  fn build_frames(&mut self, elapsed: f64) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+    let mut frames = Vec::new();
+    let shared = self.network.shared_area_frames;
+
+    if self.network.acknowledgments {
+      self.baselines.capture(
+        self.tick,
+        &self.worlds_manager.worlds,
+        self.players_manager.pack_players(),
+        self.players_manager.pack_effects(),
+      );
+    }
 
     let mut built_areas: HashMap<(String, u64), Vec<u8>> = HashMap::new();
+    let mut compressed_areas: HashMap<((String, u64), Codec), Vec<u8>> = HashMap::new();
+    let mut packed_areas: HashMap<(String, u64), BTreeMap<u64, PackedEntity>> = HashMap::new();
+    let mut packed_players: Option<BTreeMap<u32, PackedPlayer>> = None;
 
     for (key, value) in self.network_bus.area_clients.iter_mut() {
+      compact_packages(&mut value.items);
       let mut proto_buffer = Vec::new();
       if let Ok(_) = prost::Message::encode(value, &mut proto_buffer) {
         built_areas.insert(key.clone(), proto_buffer);
       }
-      value.items.clear();
     }
 
     for (index, client) in self.network_bus.direct_clients.iter_mut() {
       if let Some(hero) = self.players_manager.players.get(index) {
-        self.proto_buffer.clear();
         let player = hero.player();
-        let key = env.create_string(index.to_string())?;
-        if let Some(buffer) = built_areas.get(&(player.world.clone(), player.area)) {
-          self.proto_buffer = buffer.clone();
-          if let Ok(_) = prost::Message::encode(&client.packages, &mut self.proto_buffer) {
-            let mut slice = self.proto_buffer.as_slice();
-            let mut compressor = FrameEncoder::new(Vec::new());
-            io::copy(&mut slice, &mut compressor)?;
-            if let Ok(buffer) = compressor.finish() {
-              let uint8 = Uint8ArraySlice::from_data(env, buffer)?;
-              object.set_property(key, uint8)?;
+        let area_key = (player.world.clone(), player.area);
+        let area_items = match self.network_bus.area_clients.get(&area_key) {
+          Some(packages) => packages.items.as_slice(),
+          None => &[],
+        };
+
+        // Throttled clients hold on to their area packages until their next frame
+        client.since_send += elapsed;
+        if !consume_interval(&mut client.since_send, client.send_interval) {
+          if !client.resync {
+            client.pending_area.extend_from_slice(area_items);
+          }
+          continue;
+        }
+
+        // The area is rebuilt from the current state, so no older area package may follow it
+        let resynced = std::mem::take(&mut client.resync);
+        let mut area_items = area_items;
+        if resynced {
+          client.pending_area.clear();
+          client.view.reset();
+          area_items = &[];
+          if self.network.acknowledgments {
+            self.baselines.remove_client(*index);
+          } else if let Some(world) = self.worlds_manager.worlds.get(&player.world) {
+            let state = self.players_manager.pack_area_state(world, player, client.view_radius);
+            if client.view_radius.is_some() {
+              client.view.enter(&state);
             }
+            let state = state.into_iter().map(|kind| Package { kind: Some(kind) });
+            client.packages.items.splice(0..0, state);
+          }
+        }
+
+        if self.network.acknowledgments {
+          let (baseline, packages) =
+            self.baselines.packages(*index, &self.worlds_manager.worlds, client.view_radius);
+          for package in packages {
+            client.packages.items.push(Package {
+              kind: Some(package),
+            });
           }
+          client.packages.baseline = baseline;
+        }
+
+        let pending = if client.pending_area.is_empty() {
+          None
         } else {
-          self.proto_buffer = Vec::new();
-          if let Ok(_) = prost::Message::encode(&client.packages, &mut self.proto_buffer) {
-            let mut slice = self.proto_buffer.as_slice();
-            let mut compressor = FrameEncoder::new(Vec::new());
-            io::copy(&mut slice, &mut compressor)?;
-            if let Ok(buffer) = compressor.finish() {
-              let uint8 = Uint8ArraySlice::from_data(env, buffer)?;
-              object.set_property(key, uint8)?;
+          let mut items = std::mem::take(&mut client.pending_area);
+          items.extend_from_slice(area_items);
+          compact_packages(&mut items);
+          Some(items)
+        };
+        let area_items = pending.as_deref().unwrap_or(area_items);
+
+        let share = shared && client.codec.concatenable() && pending.is_none();
+        let mut pending_frame = Vec::new();
+
+        // Culled clients get their own filtered copy of the area packages
+        let view_radius = client.view_radius.filter(|_| !self.network.acknowledgments);
+        let area = match view_radius {
+          Some(radius) => {
+            let players =
+              packed_players.get_or_insert_with(|| self.players_manager.pack_players());
+            let entities = packed_areas.entry(area_key.clone()).or_insert_with(|| {
+              match self.worlds_manager.worlds.get(&player.world) {
+                Some(world) => world.areas[player.area as usize].get_packed_entities(),
+                None => BTreeMap::new(),
+              }
+            });
+            if let Some(me) = players.get(&(*index as u32)) {
+              let view = View::around(me, radius);
+              client.view.cull(
+                &view,
+                me,
+                entities,
+                players,
+                area_items,
+                &mut client.packages.items,
+              );
+            }
+            None
+          }
+          None if resynced => None,
+          None if pending.is_some() => {
+            let packages = Packages {
+              items: area_items.to_vec(),
+              tick: 0,
+              baseline: 0,
+            };
+            match prost::Message::encode(&packages, &mut pending_frame) {
+              Ok(_) => Some(&pending_frame),
+              Err(_) => None,
             }
           }
+          None => built_areas.get(&area_key),
+        };
+
+        client.packages.items.push(Package {
+          kind: Some(Kind::Reconciliation(Reconciliation {
+            sequence: client.processed_sequence,
+            tick: self.tick,
+            x: player.pos.x,
+            y: player.pos.y,
+            vel_x: player.vel.x,
+            vel_y: player.vel.y,
+          })),
+        });
+
+        compact_packages(&mut client.packages.items);
+        client.packages.tick = self.tick;
+        self.proto_buffer.clear();
+        if let (false, Some(area)) = (share, area) {
+          self.proto_buffer.extend_from_slice(area);
+        }
+        if let Ok(_) = prost::Message::encode(&client.packages, &mut self.proto_buffer) {
+          let compression = &mut self.network_bus.compression;
+          let stats = &mut self.network_bus.stats;
+          let direct = compression.compress(client.codec, &self.proto_buffer, stats)?;
+          let buffer = match (share, area) {
+            (true, Some(area)) => {
+              let frame = match compressed_areas.entry((area_key, client.codec)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                  entry.insert(compression.compress(client.codec, area, stats)?)
+                }
+              };
+              [frame.as_slice(), direct.as_slice()].concat()
+            }
+            _ => direct,
+          };
+          stats.sent_bytes += buffer.len() as i64;
+          frames.push((*index, buffer));
         }
       }
+      client.clear_packages();
     }
 
-    self.network_bus.clear_packages();
+    for packages in self.network_bus.area_clients.values_mut() {
+      packages.items.clear();
+    }
 
-    Ok(object)
+    Ok(frames)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DELTA: f64 = 1000.0 / 30.0;
+
+  type Frames = Vec<Vec<(i64, Vec<u8>)>>;
+
+  fn engine() -> ComputeEngine {
+    let config = include_str!("../test/config.json").to_string();
+    let worlds = vec![
+      include_str!("../test/worlds/world.json").to_string(),
+      include_str!("../test/worlds/world2.json").to_string(),
+    ];
+    ComputeEngine::create(&EngineProps::new(config, worlds, None), Some(7)).unwrap()
+  }
+
+  fn join(engine: &mut ComputeEngine) {
+    engine.join(&JoinProps::new("first".to_string(), 1, None, None, None)).unwrap();
+    engine.join(&JoinProps::new("second".to_string(), 2, None, None, Some(300.0))).unwrap();
+  }
+
+  fn play(engine: &mut ComputeEngine, ticks: std::ops::Range<u32>) -> Frames {
+    let mut result = Vec::new();
+    for tick in ticks {
+      let mut input = Input::new();
+      input.right = tick % 40 < 30;
+      input.down = tick % 20 < 10;
+      input.first_ability = tick % 50 == 0;
+      input.sequence = tick;
+      engine.input(1, &input);
+      match tick {
+        30 => engine.chat_message("hello".to_string(), 2),
+        45 => engine.set_send_rate(2, Some(10.0)),
+        60 => engine.set_view_radius(2, Some(150.0)),
+        90 => engine.leave(1),
+        _ => {}
+      }
+      // Uneven deltas run zero, one or several fixed steps per update
+      let delta = DELTA * (tick % 3) as f64;
+      engine.advance(delta);
+      result.push(sorted(engine.frames(delta).unwrap()));
+    }
+    result
+  }
+
+  fn replay(recording: Vec<u8>, chunks: Vec<Vec<u8>>) -> Frames {
+    let mut engine = ComputeEngine::from_recording(Uint8Array::new(recording)).unwrap();
+    for chunk in chunks {
+      engine.append_recording(Uint8Array::new(chunk)).unwrap();
+    }
+    let mut result = Vec::new();
+    while let Some(delta) = engine.replay_step().unwrap() {
+      result.push(sorted(engine.frames(delta).unwrap()));
+    }
+    result
+  }
+
+  fn sorted(mut frames: Vec<(i64, Vec<u8>)>) -> Vec<(i64, Vec<u8>)> {
+    frames.sort_by_key(|(id, _)| *id);
+    frames
+  }
+
+  #[test]
+  fn replay_reproduces_recorded_frames() {
+    let mut engine = engine();
+    engine.start_recording().unwrap();
+    join(&mut engine);
+    let recorded = play(&mut engine, 0..120);
+    let bytes = engine.export_recording().unwrap().to_vec();
+
+    assert!(recorded.iter().any(|frames| !frames.is_empty()));
+    assert_eq!(recorded, replay(bytes, Vec::new()));
+  }
+
+  #[test]
+  fn replay_of_flushed_mid_session_recording_reproduces_recorded_frames() {
+    let mut engine = engine();
+    join(&mut engine);
+    play(&mut engine, 0..20);
+    let snapshot = engine.snapshot().unwrap().to_vec();
+    play(&mut engine, 20..40);
+
+    engine.start_recording().unwrap();
+    engine.set_zstd_dictionary(Uint8Array::new(vec![7; 64]));
+    let mut recorded = play(&mut engine, 40..70);
+    let first = engine.flush_recording().unwrap().to_vec();
+    engine.restore(Uint8Array::new(snapshot)).unwrap();
+    recorded.extend(play(&mut engine, 70..120));
+    let second = engine.flush_recording().unwrap().to_vec();
+
+    assert!(engine.export_recording().is_err());
+    assert_eq!(recorded, replay(first, vec![second]));
+  }
+
+  #[test]
+  fn rejected_join_is_not_recorded() {
+    let mut engine = engine();
+    engine.start_recording().unwrap();
+    let hero = Some("Unknown".to_string());
+    assert!(engine.join(&JoinProps::new("ghost".to_string(), 3, hero, None, None)).is_err());
+    join(&mut engine);
+    let recorded = play(&mut engine, 0..40);
+    let bytes = engine.export_recording().unwrap().to_vec();
+
+    assert_eq!(recorded, replay(bytes, Vec::new()));
+  }
+
+  #[test]
+  fn starting_a_recording_leaves_the_session_untouched() {
+    let mut engine = engine();
+    join(&mut engine);
+    play(&mut engine, 0..20);
+    let before = engine.snapshot().unwrap().to_vec();
+    engine.start_recording().unwrap();
+
+    assert_eq!(before, engine.snapshot().unwrap().to_vec());
+  }
+
+  #[test]
+  fn restoring_a_snapshot_continues_the_same_random_sequence() {
+    let mut engine = engine();
+    join(&mut engine);
+    play(&mut engine, 0..20);
+    let snapshot = engine.snapshot().unwrap().to_vec();
+    let expected = crate::resources::random(0.0, 1.0);
+    play(&mut engine, 20..40);
+    engine.restore(Uint8Array::new(snapshot.clone())).unwrap();
+
+    assert_eq!(snapshot, engine.snapshot().unwrap().to_vec());
+    assert_eq!(expected, crate::resources::random(0.0, 1.0));
+  }
+
+  #[test]
+  fn rejected_snapshot_leaves_the_engine_untouched() {
+    let mut engine = engine();
+    join(&mut engine);
+    play(&mut engine, 0..20);
+    let mut snapshot = engine.capture();
+    snapshot.worlds[0].name = "Missing".to_string();
+    snapshot.tick = 1000;
+    let bytes = snapshot.encode().unwrap();
+    let before = engine.snapshot().unwrap().to_vec();
+
+    assert!(engine.restore(Uint8Array::new(bytes)).is_err());
+    assert_eq!(engine.tick, 20);
+    assert_eq!(before, engine.snapshot().unwrap().to_vec());
+  }
+
+  #[test]
+  fn restore_returns_clients_without_a_player_in_the_snapshot() {
+    let mut engine = engine();
+    engine.join(&JoinProps::new("first".to_string(), 1, None, None, None)).unwrap();
+    let snapshot = engine.snapshot().unwrap().to_vec();
+    engine.join(&JoinProps::new("second".to_string(), 2, None, None, None)).unwrap();
+
+    assert_eq!(engine.restore(Uint8Array::new(snapshot)).unwrap(), vec![2]);
+    assert!(!engine.network_bus.direct_clients.contains_key(&2));
   }
 }
@@ -0,0 +1,446 @@
+use crate::managers::interest::View;
+use crate::proto::package::Kind;
+use crate::proto::{
+  CloseEntities, Entities, PackedEffect, PackedEntity, PackedPlayer, Players, RemovedEffect,
+  UpdateEntitiesMap, UpdatePlayersMap, ViewEnter, ViewLeave,
+};
+use crate::resources::world::World;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Frame {
+  tick: u64,
+  areas: HashMap<(String, u64), BTreeMap<u64, PackedEntity>>,
+  players: BTreeMap<u32, PackedPlayer>,
+  effects: BTreeMap<(u32, u32, u32), PackedEffect>,
+}
+
+impl Frame {
+  fn has_player_in(&self, id: u32, world: &str, area: u32) -> bool {
+    match self.players.get(&id) {
+      Some(player) => player.world == world && player.area == area,
+      None => false,
+    }
+  }
+
+  // The effects on the players sharing the viewer's area in this frame
+  fn effects_around<'a>(
+    &'a self,
+    viewer: &'a PackedPlayer,
+  ) -> impl Iterator<Item = (&'a (u32, u32, u32), &'a PackedEffect)> + 'a {
+    self.effects.iter().filter(move |(_, effect)| {
+      let target = self.players.get(&effect.player_id);
+      target.is_some_and(|target| shares_area(viewer, target))
+    })
+  }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct ClientBaseline {
+  acked: Option<u64>,
+  resync: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BaselinesManager {
+  frames: VecDeque<Frame>,
+  window: usize,
+  clients: HashMap<i64, ClientBaseline>,
+}
+
+impl BaselinesManager {
+  pub fn new(window: usize) -> Self {
+    Self {
+      frames: VecDeque::new(),
+      window: window.max(1),
+      clients: HashMap::new(),
+    }
+  }
+
+  pub fn ack(&mut self, id: i64, tick: u64) {
+    let latest = match self.frames.back() {
+      Some(frame) => frame.tick,
+      None => return,
+    };
+    if tick > latest {
+      return;
+    }
+    let client = self.clients.entry(id).or_default();
+    if client.acked.is_none_or(|acked| tick > acked) {
+      client.acked = Some(tick);
+    }
+  }
+
+  pub fn remove_client(&mut self, id: i64) {
+    self.clients.remove(&id);
+  }
+
+  pub fn clear(&mut self) {
+    self.frames.clear();
+    self.clients.clear();
+  }
+
+  // Stores the state every client will be diffed against once it acknowledges this tick
+  pub fn capture(
+    &mut self,
+    tick: u64,
+    worlds: &HashMap<String, World>,
+    players: BTreeMap<u32, PackedPlayer>,
+    effects: BTreeMap<(u32, u32, u32), PackedEffect>,
+  ) {
+    let mut areas = HashMap::new();
+    for (name, world) in worlds.iter() {
+      for (index, area) in world.areas.iter().enumerate() {
+        if !area.players_id.is_empty() {
+          areas.insert((name.clone(), index as u64), area.get_packed_entities());
+        }
+      }
+    }
+
+    if self.frames.back().is_some_and(|frame| frame.tick == tick) {
+      self.frames.pop_back();
+    }
+    self.frames.push_back(Frame {
+      tick,
+      areas,
+      players,
+      effects,
+    });
+    while self.frames.len() > self.window {
+      self.frames.pop_front();
+    }
+  }
+
+  // Returns the baseline tick the packages are relative to, 0 when a full resync is sent
+  pub fn packages(
+    &mut self,
+    id: i64,
+    worlds: &HashMap<String, World>,
+    view_radius: Option<f64>,
+  ) -> (u64, Vec<Kind>) {
+    let current = match self.frames.back() {
+      Some(frame) => frame,
+      None => return (0, Vec::new()),
+    };
+    let player = match current.players.get(&(id as u32)) {
+      Some(player) => player,
+      None => return (0, Vec::new()),
+    };
+
+    let client = self.clients.entry(id).or_default();
+    let baseline = [client.acked, client.resync]
+      .into_iter()
+      .flatten()
+      .filter_map(|tick| self.frames.iter().find(|frame| frame.tick == tick))
+      .find(|frame| frame.has_player_in(id as u32, &player.world, player.area));
+
+    let area_key = (player.world.clone(), player.area as u64);
+    let baseline = match baseline {
+      Some(baseline) => baseline,
+      None => {
+        client.resync = Some(current.tick);
+        let view = view_radius.map(|radius| View::around(player, radius));
+        let mut packages = Vec::new();
+        if let Some(world) = worlds.get(&player.world) {
+          let mut area = world.pack_area(player.area as usize);
+          if let Some(view) = view {
+            area.entities.retain(|_, entity| view.sees_entity(entity));
+          }
+          packages.push(Kind::AreaInit(area));
+        }
+        let players = current
+          .players
+          .iter()
+          .filter(|(_, other)| shares_area(player, other))
+          .filter(|(_, other)| view.is_none_or(|view| view.sees_player(player, other)))
+          .map(|(id, other)| (*id, other.clone()))
+          .collect();
+        packages.push(Kind::Players(Players { players }));
+        for (_, effect) in current.effects_around(player) {
+          packages.push(Kind::AddEffect(*effect));
+        }
+        return (0, packages);
+      }
+    };
+
+    let mut packages = Vec::new();
+    if baseline.tick == current.tick {
+      return (baseline.tick, packages);
+    }
+
+    // A resync was culled the same way, so its frame works as a baseline like any other
+    let old_me = &baseline.players[&(id as u32)];
+    let old_view = view_radius.map(|radius| View::around(old_me, radius));
+    let new_view = view_radius.map(|radius| View::around(player, radius));
+
+    let empty = BTreeMap::new();
+    let old_entities = baseline.areas.get(&area_key).unwrap_or(&empty);
+    let new_entities = current.areas.get(&area_key).unwrap_or(&empty);
+
+    let mut spawned_entities = BTreeMap::new();
+    let mut entities_diff = BTreeMap::new();
+    let mut enter = ViewEnter::default();
+    let mut leave = ViewLeave::default();
+    for (entity_id, entity) in new_entities.iter() {
+      let seen = new_view.is_none_or(|view| view.sees_entity(entity));
+      match old_entities.get(entity_id) {
+        Some(old_entity) => {
+          let was_seen = old_view.is_none_or(|view| view.sees_entity(old_entity));
+          match (was_seen, seen) {
+            (true, true) => {
+              let (diff, changed) = old_entity.diff(entity);
+              if changed {
+                entities_diff.insert(*entity_id as u32, diff);
+              }
+            }
+            (false, true) => {
+              enter.entities.insert(*entity_id as u32, *entity);
+            }
+            (true, false) => leave.entities.push(*entity_id as u32),
+            (false, false) => {}
+          }
+        }
+        None if seen => {
+          spawned_entities.insert(*entity_id as u32, *entity);
+        }
+        None => {}
+      }
+    }
+    let removed_entities: Vec<u32> = old_entities
+      .iter()
+      .filter(|(entity_id, _)| !new_entities.contains_key(entity_id))
+      .filter(|(_, entity)| old_view.is_none_or(|view| view.sees_entity(entity)))
+      .map(|(entity_id, _)| *entity_id as u32)
+      .collect();
+
+    if !spawned_entities.is_empty() {
+      packages.push(Kind::NewEntities(Entities {
+        entities: spawned_entities,
+      }));
+    }
+    if !removed_entities.is_empty() {
+      packages.push(Kind::CloseEntities(CloseEntities {
+        ids: removed_entities,
+      }));
+    }
+    if !entities_diff.is_empty() {
+      packages.push(Kind::UpdateEntities(UpdateEntitiesMap {
+        items: entities_diff,
+      }));
+    }
+
+    let mut players_diff = BTreeMap::new();
+    for (player_id, other) in current.players.iter() {
+      if !shares_area(player, other) {
+        continue;
+      }
+      match baseline.players.get(player_id).filter(|old| shares_area(old_me, old)) {
+        Some(old_player) => {
+          let was_seen = old_view.is_none_or(|view| view.sees_player(old_me, old_player));
+          let seen = new_view.is_none_or(|view| view.sees_player(player, other));
+          match (was_seen, seen) {
+            (true, true) => {
+              let (diff, changed) = old_player.diff(other);
+              if changed {
+                players_diff.insert(*player_id, diff);
+              }
+            }
+            (false, true) => {
+              enter.players.insert(*player_id, other.clone());
+            }
+            (true, false) => leave.players.push(*player_id),
+            (false, false) => {}
+          }
+        }
+        None => packages.push(Kind::NewPlayer(other.clone())),
+      }
+    }
+    for (player_id, old_player) in baseline.players.iter() {
+      let stayed = current
+        .players
+        .get(player_id)
+        .is_some_and(|other| shares_area(player, other));
+      if shares_area(old_me, old_player) && !stayed {
+        packages.push(Kind::ClosePlayer(*player_id as i64));
+      }
+    }
+    if !players_diff.is_empty() {
+      packages.push(Kind::UpdatePlayers(UpdatePlayersMap {
+        items: players_diff,
+      }));
+    }
+
+    // Effects are diffed like the rest of the state, so a dropped frame cannot lose one
+    let old_effects: BTreeMap<_, _> = baseline.effects_around(old_me).collect();
+    for (key, effect) in current.effects_around(player) {
+      match old_effects.get(key) {
+        Some(old_effect) if old_effect.changed(effect) => {
+          packages.push(Kind::UpdateEffect(*effect))
+        }
+        Some(_) => {}
+        None => packages.push(Kind::AddEffect(*effect)),
+      }
+    }
+    for (key, effect) in old_effects.iter() {
+      let stayed = current
+        .players
+        .get(&effect.player_id)
+        .is_some_and(|other| shares_area(player, other));
+      if stayed && !current.effects.contains_key(*key) {
+        packages.push(Kind::RemoveEffect(RemovedEffect {
+          player_id: effect.player_id,
+          effect_type: effect.effect_type,
+          instance: effect.instance,
+        }));
+      }
+    }
+    if !enter.entities.is_empty() || !enter.players.is_empty() {
+      packages.push(Kind::ViewEnter(enter));
+    }
+    if !leave.entities.is_empty() || !leave.players.is_empty() {
+      packages.push(Kind::ViewLeave(leave));
+    }
+
+    (baseline.tick, packages)
+  }
+}
+
+// Players only get full detail about the players in their own area
+fn shares_area(me: &PackedPlayer, other: &PackedPlayer) -> bool {
+  me.world == other.world && me.area == other.area
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn player(id: u32, x: i32, area: u32) -> (u32, PackedPlayer) {
+    let packed = PackedPlayer {
+      id,
+      x,
+      area,
+      world: "world".to_string(),
+      ..Default::default()
+    };
+    (id, packed)
+  }
+
+  fn effect(player_id: u32, stacks: u32) -> ((u32, u32, u32), PackedEffect) {
+    let packed = PackedEffect {
+      player_id,
+      effect_type: 1,
+      stacks,
+      ..Default::default()
+    };
+    ((player_id, 1, 0), packed)
+  }
+
+  fn capture(
+    baselines: &mut BaselinesManager,
+    tick: u64,
+    players: Vec<(u32, PackedPlayer)>,
+    effects: Vec<((u32, u32, u32), PackedEffect)>,
+  ) {
+    let worlds = HashMap::new();
+    let players = players.into_iter().collect();
+    let effects = effects.into_iter().collect();
+    baselines.capture(tick, &worlds, players, effects);
+  }
+
+  fn packages(baselines: &mut BaselinesManager, id: i64) -> (u64, Vec<Kind>) {
+    baselines.packages(id, &HashMap::new(), None)
+  }
+
+  #[test]
+  fn unacknowledged_client_gets_a_full_resync_of_its_area() {
+    let mut baselines = BaselinesManager::new(8);
+    let players = vec![player(1, 0, 0), player(2, 10, 0), player(3, 20, 1)];
+    capture(&mut baselines, 1, players, vec![effect(2, 1), effect(3, 1)]);
+
+    let (baseline, packages) = packages(&mut baselines, 1);
+    assert_eq!(baseline, 0);
+    match &packages[..] {
+      [Kind::Players(players), Kind::AddEffect(effect)] => {
+        assert_eq!(players.players.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(effect.player_id, 2);
+      }
+      _ => panic!("expected a resync of area 0"),
+    }
+  }
+
+  #[test]
+  fn acknowledged_client_only_gets_the_diff() {
+    let mut baselines = BaselinesManager::new(8);
+    capture(&mut baselines, 1, vec![player(1, 0, 0), player(2, 10, 0)], Vec::new());
+    baselines.ack(1, 1);
+    capture(&mut baselines, 2, vec![player(1, 5, 0), player(3, 10, 0)], Vec::new());
+
+    let (baseline, packages) = packages(&mut baselines, 1);
+    assert_eq!(baseline, 1);
+    assert!(matches!(&packages[0], Kind::NewPlayer(player) if player.id == 3));
+    assert!(matches!(&packages[1], Kind::ClosePlayer(2)));
+    match &packages[2] {
+      Kind::UpdatePlayers(update) => {
+        assert_eq!(update.items.keys().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(update.items[&1].x, Some(5));
+      }
+      _ => panic!("expected an update of player 1"),
+    }
+    assert_eq!(packages.len(), 3);
+  }
+
+  #[test]
+  fn effects_are_diffed_against_the_baseline() {
+    let mut baselines = BaselinesManager::new(8);
+    let players = vec![player(1, 0, 0), player(2, 10, 0)];
+    capture(&mut baselines, 1, players.clone(), vec![effect(1, 1), effect(2, 1)]);
+    baselines.ack(1, 1);
+    capture(&mut baselines, 2, players, vec![effect(1, 2)]);
+
+    let (_, packages) = packages(&mut baselines, 1);
+    assert!(matches!(&packages[0], Kind::UpdateEffect(effect) if effect.player_id == 1));
+    assert!(matches!(&packages[1], Kind::RemoveEffect(effect) if effect.player_id == 2));
+    assert_eq!(packages.len(), 2);
+  }
+
+  #[test]
+  fn up_to_date_client_gets_nothing() {
+    let mut baselines = BaselinesManager::new(8);
+    capture(&mut baselines, 1, vec![player(1, 0, 0)], Vec::new());
+    baselines.ack(1, 1);
+
+    assert_eq!(packages(&mut baselines, 1), (1, Vec::new()));
+  }
+
+  #[test]
+  fn resync_works_as_a_baseline_until_acknowledged() {
+    let mut baselines = BaselinesManager::new(8);
+    capture(&mut baselines, 1, vec![player(1, 0, 0)], Vec::new());
+    assert_eq!(packages(&mut baselines, 1).0, 0);
+    capture(&mut baselines, 2, vec![player(1, 5, 0)], Vec::new());
+
+    assert_eq!(packages(&mut baselines, 1).0, 1);
+  }
+
+  #[test]
+  fn baseline_outside_the_window_triggers_a_resync() {
+    let mut baselines = BaselinesManager::new(2);
+    capture(&mut baselines, 1, vec![player(1, 0, 0)], Vec::new());
+    baselines.ack(1, 1);
+    for tick in 2..5 {
+      capture(&mut baselines, tick, vec![player(1, tick as i32, 0)], Vec::new());
+    }
+
+    assert_eq!(packages(&mut baselines, 1).0, 0);
+  }
+
+  #[test]
+  fn acknowledging_a_future_tick_is_ignored() {
+    let mut baselines = BaselinesManager::new(8);
+    capture(&mut baselines, 1, vec![player(1, 0, 0)], Vec::new());
+    baselines.ack(1, 5);
+
+    assert_eq!(packages(&mut baselines, 1).0, 0);
+  }
+}
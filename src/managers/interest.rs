@@ -0,0 +1,348 @@
+use crate::proto::package::Kind;
+use crate::proto::{
+  CloseEntities, Entities, Package, PackedEntity, PackedPlayer, UpdateEntitiesMap, ViewEnter,
+  ViewLeave,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Copy)]
+pub struct View {
+  x: f64,
+  y: f64,
+  radius: f64,
+}
+
+impl View {
+  pub fn around(player: &PackedPlayer, radius: f64) -> Self {
+    Self {
+      x: player.x as f64 / 2.0,
+      y: player.y as f64 / 2.0,
+      radius,
+    }
+  }
+
+  pub fn sees_entity(&self, entity: &PackedEntity) -> bool {
+    self.sees(entity.x, entity.y, entity.radius)
+  }
+
+  pub fn sees_player(&self, viewer: &PackedPlayer, player: &PackedPlayer) -> bool {
+    player.id == viewer.id
+      || (player.world == viewer.world
+        && player.area == viewer.area
+        && self.sees(player.x, player.y, player.radius))
+  }
+
+  // Packed coordinates are stored at double precision
+  fn sees(&self, x: i32, y: i32, radius: u32) -> bool {
+    let dx = x as f64 / 2.0 - self.x;
+    let dy = y as f64 / 2.0 - self.y;
+    let reach = self.radius + radius as f64 / 2.0;
+    dx * dx + dy * dy <= reach * reach
+  }
+}
+
+// What a culled client currently knows about, so enter/leave messages can be derived
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ViewState {
+  area: Option<(String, u64)>,
+  entities: BTreeSet<u32>,
+  players: BTreeSet<u32>,
+}
+
+impl ViewState {
+  pub fn reset(&mut self) {
+    self.area = None;
+    self.entities.clear();
+    self.players.clear();
+  }
+
+  // Starts tracking from the culled area state the client was just sent
+  pub fn enter(&mut self, state: &[Kind]) {
+    self.reset();
+    for kind in state.iter() {
+      match kind {
+        Kind::AreaInit(area) => {
+          self.area = Some((area.world.clone(), area.area));
+          self.entities = area.entities.keys().map(|id| *id as u32).collect();
+        }
+        Kind::Players(players) => self.players = players.players.keys().copied().collect(),
+        _ => {}
+      }
+    }
+  }
+
+  pub fn cull(
+    &mut self,
+    view: &View,
+    me: &PackedPlayer,
+    entities: &BTreeMap<u64, PackedEntity>,
+    players: &BTreeMap<u32, PackedPlayer>,
+    area_items: &[Package],
+    items: &mut Vec<Package>,
+  ) {
+    // Without a culled resync the client got the whole area when it entered it
+    let area = (me.world.clone(), me.area as u64);
+    if self.area.as_ref() != Some(&area) {
+      self.area = Some(area);
+      self.entities = entities.keys().map(|id| *id as u32).collect();
+      self.players = players
+        .iter()
+        .filter(|(_, player)| player.world == me.world && player.area == me.area)
+        .map(|(id, _)| *id)
+        .collect();
+    }
+
+    let visible_entities: BTreeSet<u32> = entities
+      .iter()
+      .filter(|(_, entity)| view.sees_entity(entity))
+      .map(|(id, _)| *id as u32)
+      .collect();
+    let visible_players: BTreeSet<u32> = players
+      .iter()
+      .filter(|(_, player)| view.sees_player(me, player))
+      .map(|(id, _)| *id)
+      .collect();
+
+    let mut spawned = BTreeSet::new();
+    let mut arrived = BTreeSet::new();
+    let mut departed = BTreeSet::new();
+    for package in area_items.iter() {
+      match &package.kind {
+        Some(Kind::NewEntities(new)) => {
+          let entities: BTreeMap<u32, PackedEntity> = new
+            .entities
+            .iter()
+            .filter(|(id, _)| visible_entities.contains(id))
+            .map(|(id, entity)| (*id, *entity))
+            .collect();
+          if !entities.is_empty() {
+            spawned.extend(entities.keys().copied());
+            items.push(Package {
+              kind: Some(Kind::NewEntities(Entities { entities })),
+            });
+          }
+        }
+        Some(Kind::CloseEntities(close)) => {
+          let ids: Vec<u32> = close
+            .ids
+            .iter()
+            .filter(|id| self.entities.contains(id))
+            .copied()
+            .collect();
+          if !ids.is_empty() {
+            items.push(Package {
+              kind: Some(Kind::CloseEntities(CloseEntities { ids })),
+            });
+          }
+        }
+        Some(Kind::UpdateEntities(update)) => {
+          let diff: BTreeMap<_, _> = update
+            .items
+            .iter()
+            .filter(|(id, _)| self.entities.contains(id) && visible_entities.contains(id))
+            .map(|(id, entity)| (*id, *entity))
+            .collect();
+          if !diff.is_empty() {
+            items.push(Package {
+              kind: Some(Kind::UpdateEntities(UpdateEntitiesMap { items: diff })),
+            });
+          }
+        }
+        Some(Kind::NewPlayer(player)) => {
+          if visible_players.contains(&player.id) {
+            arrived.insert(player.id);
+            items.push(package.clone());
+          }
+        }
+        Some(Kind::ClosePlayer(id)) => {
+          if self.players.contains(&(*id as u32)) {
+            departed.insert(*id as u32);
+            items.push(package.clone());
+          }
+        }
+        _ => items.push(package.clone()),
+      }
+    }
+
+    for package in items.iter_mut() {
+      if let Some(Kind::UpdatePlayers(update)) = &mut package.kind {
+        update
+          .items
+          .retain(|id, _| self.players.contains(id) && visible_players.contains(id));
+      }
+    }
+    items.retain(|package| match &package.kind {
+      Some(Kind::UpdatePlayers(update)) => !update.items.is_empty(),
+      _ => true,
+    });
+
+    let mut enter = ViewEnter::default();
+    let mut leave = ViewLeave::default();
+    for id in visible_entities.iter() {
+      if !self.entities.contains(id) && !spawned.contains(id) {
+        enter.entities.insert(*id, entities[&(*id as u64)]);
+      }
+    }
+    for id in self.entities.iter() {
+      if !visible_entities.contains(id) && entities.contains_key(&(*id as u64)) {
+        leave.entities.push(*id);
+      }
+    }
+    for id in visible_players.iter() {
+      if !self.players.contains(id) && !arrived.contains(id) {
+        enter.players.insert(*id, players[id].clone());
+      }
+    }
+    for id in self.players.iter() {
+      if !visible_players.contains(id) && !departed.contains(id) && players.contains_key(id) {
+        leave.players.push(*id);
+      }
+    }
+
+    if !enter.entities.is_empty() || !enter.players.is_empty() {
+      items.push(Package {
+        kind: Some(Kind::ViewEnter(enter)),
+      });
+    }
+    if !leave.entities.is_empty() || !leave.players.is_empty() {
+      items.push(Package {
+        kind: Some(Kind::ViewLeave(leave)),
+      });
+    }
+
+    self.entities = visible_entities;
+    self.players = visible_players;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::proto::PartialEntity;
+
+  // Packed coordinates are doubled, so x is given in world units here
+  fn entity(x: i32) -> PackedEntity {
+    PackedEntity {
+      x: x * 2,
+      radius: 10,
+      ..Default::default()
+    }
+  }
+
+  fn player(id: u32, x: i32) -> PackedPlayer {
+    PackedPlayer {
+      id,
+      x: x * 2,
+      radius: 10,
+      world: "world".to_string(),
+      ..Default::default()
+    }
+  }
+
+  fn cull(
+    state: &mut ViewState,
+    entities: &BTreeMap<u64, PackedEntity>,
+    players: &BTreeMap<u32, PackedPlayer>,
+    area_items: &[Package],
+  ) -> Vec<Package> {
+    let me = &players[&1];
+    let view = View::around(me, 100.0);
+    let mut items = Vec::new();
+    state.cull(&view, me, entities, players, area_items, &mut items);
+    items
+  }
+
+  fn kinds(items: Vec<Package>) -> Vec<Kind> {
+    items.into_iter().filter_map(|package| package.kind).collect()
+  }
+
+  #[test]
+  fn entering_an_area_leaves_what_is_out_of_view() {
+    let mut state = ViewState::default();
+    let entities = BTreeMap::from([(1, entity(50)), (2, entity(500))]);
+    let players = BTreeMap::from([(1, player(1, 0)), (2, player(2, 500))]);
+
+    match &kinds(cull(&mut state, &entities, &players, &[]))[..] {
+      [Kind::ViewLeave(leave)] => {
+        assert_eq!(leave.entities, vec![2]);
+        assert_eq!(leave.players, vec![2]);
+      }
+      kinds => panic!("expected a single leave, got {}", kinds.len()),
+    }
+  }
+
+  #[test]
+  fn moving_into_view_enters_and_out_of_view_leaves() {
+    let mut state = ViewState::default();
+    let players = BTreeMap::from([(1, player(1, 0))]);
+    cull(&mut state, &BTreeMap::from([(1, entity(500))]), &players, &[]);
+
+    let entities = BTreeMap::from([(1, entity(50))]);
+    match &kinds(cull(&mut state, &entities, &players, &[]))[..] {
+      [Kind::ViewEnter(enter)] => assert!(enter.entities.contains_key(&1)),
+      _ => panic!("expected the entity to enter the view"),
+    }
+    assert!(kinds(cull(&mut state, &entities, &players, &[])).is_empty());
+
+    let entities = BTreeMap::from([(1, entity(500))]);
+    match &kinds(cull(&mut state, &entities, &players, &[]))[..] {
+      [Kind::ViewLeave(leave)] => assert_eq!(leave.entities, vec![1]),
+      _ => panic!("expected the entity to leave the view"),
+    }
+  }
+
+  #[test]
+  fn area_packages_are_culled_to_the_view() {
+    let mut state = ViewState::default();
+    let players = BTreeMap::from([(1, player(1, 0))]);
+    let entities = BTreeMap::from([(1, entity(50)), (2, entity(500))]);
+    cull(&mut state, &entities, &players, &[]);
+
+    let entities = BTreeMap::from([
+      (1, entity(50)),
+      (2, entity(500)),
+      (3, entity(60)),
+      (4, entity(600)),
+    ]);
+    let spawned = Entities {
+      entities: BTreeMap::from([(3, entity(60)), (4, entity(600))]),
+    };
+    let update = UpdateEntitiesMap {
+      items: BTreeMap::from([(1, PartialEntity::default()), (2, PartialEntity::default())]),
+    };
+    let area_items = [
+      Package {
+        kind: Some(Kind::NewEntities(spawned)),
+      },
+      Package {
+        kind: Some(Kind::UpdateEntities(update)),
+      },
+    ];
+    match &kinds(cull(&mut state, &entities, &players, &area_items))[..] {
+      [Kind::NewEntities(spawned), Kind::UpdateEntities(update)] => {
+        assert_eq!(spawned.entities.keys().copied().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(update.items.keys().copied().collect::<Vec<_>>(), vec![1]);
+      }
+      _ => panic!("expected only the visible spawn and update"),
+    }
+  }
+
+  #[test]
+  fn players_enter_and_leave_the_view() {
+    let mut state = ViewState::default();
+    let entities = BTreeMap::new();
+    let far = BTreeMap::from([(1, player(1, 0)), (2, player(2, 500))]);
+    cull(&mut state, &entities, &far, &[]);
+
+    let near = BTreeMap::from([(1, player(1, 0)), (2, player(2, 50))]);
+    match &kinds(cull(&mut state, &entities, &near, &[]))[..] {
+      [Kind::ViewEnter(enter)] => assert!(enter.players.contains_key(&2)),
+      _ => panic!("expected the player to enter the view"),
+    }
+    match &kinds(cull(&mut state, &entities, &far, &[]))[..] {
+      [Kind::ViewLeave(leave)] => assert_eq!(leave.players, vec![2]),
+      _ => panic!("expected the player to leave the view"),
+    }
+  }
+}
@@ -1,2 +1,4 @@
+pub mod baseline;
+pub mod interest;
 pub mod player;
 pub mod world;
@@ -1,35 +1,41 @@
 use crate::bus::{EventBus, NetworkBus};
+use crate::managers::interest::View;
+use crate::config::RegionKind;
 use crate::proto::package::Kind;
-use crate::proto::{PackedPlayer, PartialPlayer, Players, UpdatePlayersMap};
+use crate::proto::{
+  PackedEffect, PackedPlayer, PartialPlayer, Players, Roster, RosterPlayer, UpdatePlayersMap,
+};
 use crate::resources::assets::effect::PlayerEffectWrapper;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::utils::join::JoinProps;
 use crate::resources::world::World;
 use crate::resources::{PlayerUpdateProps, UpdateProps};
 use napi::{Error, Status};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use crate::resources::player::Player;
 
 pub struct PlayersManager {
-  pub players: HashMap<i64, HeroWrapper>,
-  pub start_packages: HashMap<u32, PackedPlayer>,
-  pub end_packages: HashMap<u32, PackedPlayer>,
-  pub players_diff: HashMap<u32, PartialPlayer>,
+  pub players: BTreeMap<i64, HeroWrapper>,
+  pub start_packages: BTreeMap<u32, PackedPlayer>,
+  pub end_packages: BTreeMap<u32, PackedPlayer>,
+  pub players_diff: BTreeMap<u32, PartialPlayer>,
   pub players_to_remove: Vec<u32>,
-  pub effects: HashMap<i64, HashMap<u64, PlayerEffectWrapper>>,
+  pub effects: BTreeMap<i64, BTreeMap<(u64, u64), PlayerEffectWrapper>>,
   pub effects_to_remove: Vec<u64>,
+  pub victories: Vec<i64>,
 }
 
 impl PlayersManager {
   pub fn new() -> Self {
     Self {
-      players: HashMap::new(),
-      start_packages: HashMap::new(),
-      end_packages: HashMap::new(),
-      players_diff: HashMap::new(),
+      players: BTreeMap::new(),
+      start_packages: BTreeMap::new(),
+      end_packages: BTreeMap::new(),
+      players_diff: BTreeMap::new(),
       players_to_remove: Vec::new(),
       effects_to_remove: Vec::new(),
-      effects: HashMap::new(),
+      effects: BTreeMap::new(),
+      victories: Vec::new(),
     }
   }
 
@@ -39,30 +45,40 @@ impl PlayersManager {
     worlds: &mut HashMap<String, World>,
     network_bus: &mut NetworkBus,
   ) -> Result<(), Error> {
-    let hero = HeroWrapper::new("maven", player_props.clone())?;
+    if self.players.contains_key(&player_props.id) {
+      return self.rejoin(player_props.id, worlds, network_bus);
+    }
+
+    let mut hero = HeroWrapper::new(player_props.hero_name(), player_props.clone())?;
+    if let Some(world) = worlds.get(&hero.player().world) {
+      if let Some(area) = world.areas.get(hero.player().area as usize) {
+        if let Some(pos) = area.spawn_point(hero.player().radius) {
+          hero.player_mut().pos = pos;
+        }
+        hero.player_mut().regions = area.regions_at(hero.player());
+      }
+    }
     let player = hero.player().clone();
     let player_id = hero.player().id;
     let world_name = hero.player().world.clone();
+    let packed_player = hero.pack();
 
     self.players.insert(player_id, hero);
     if let Some(_) = self.players.get(&player_id) {
       if let Some(world) = worlds.get_mut(&world_name) {
         world.join(&player);
 
-        let packed_player = player.pack();
-
-        network_bus.add_global_package(Kind::NewPlayer(packed_player.clone()));
-        network_bus.add_direct_package(
-          player_id,
-          Kind::AreaInit(world.pack_area(player.area as usize)),
+        network_bus.add_area_package(
+          world_name.clone(),
+          player.area,
+          Kind::NewPlayer(packed_player.clone()),
         );
-
-        let players = self.pack_players();
-
-        network_bus.add_direct_package(player_id, Kind::Players(Players { players }));
+        network_bus.resync(player_id);
 
         network_bus.add_direct_package(player_id, Kind::Myself(packed_player.clone()));
 
+        network_bus.add_direct_package(player_id, Kind::Roster(self.roster()));
+
         return Ok(());
       }
     }
@@ -73,6 +89,31 @@ impl PlayersManager {
     ))
   }
 
+  fn rejoin(
+    &self,
+    player_id: i64,
+    worlds: &HashMap<String, World>,
+    network_bus: &mut NetworkBus,
+  ) -> Result<(), Error> {
+    if let Some(hero) = self.players.get(&player_id) {
+      let player = hero.player();
+      if worlds.contains_key(&player.world) {
+        network_bus.resync(player_id);
+
+        network_bus.add_direct_package(player_id, Kind::Myself(hero.pack()));
+
+        network_bus.add_direct_package(player_id, Kind::Roster(self.roster()));
+
+        return Ok(());
+      }
+    }
+
+    Err(Error::new(
+      Status::InvalidArg,
+      format!("Player not found: {}", player_id),
+    ))
+  }
+
   pub fn leave(
     &mut self,
     player_id: i64,
@@ -84,10 +125,15 @@ impl PlayersManager {
       if let Some(world) = worlds.get_mut(&player.world) {
         world.leave(&player);
       }
+      network_bus.add_area_package(
+        player.world.clone(),
+        player.area,
+        Kind::ClosePlayer(player_id),
+      );
       self.players.remove(&player_id);
+      self.effects.remove(&player_id);
     }
     network_bus.remove_client(player_id);
-    network_bus.add_global_package(Kind::ClosePlayer(player_id));
   }
 
   pub fn snapshot_start(&mut self) {
@@ -98,19 +144,23 @@ impl PlayersManager {
     self.end_packages = self.pack_players();
     self.players_diff.clear();
 
+    // Full detail only goes to the players sharing an area, the rest see the roster
+    let mut areas_diff: HashMap<(String, u64), BTreeMap<u32, PartialPlayer>> = HashMap::new();
     for (id, player) in self.end_packages.iter() {
       if let Some(old_player) = self.start_packages.get(&id) {
         let (diff, changed) = old_player.diff(&player);
         if changed {
+          areas_diff
+            .entry((player.world.clone(), player.area as u64))
+            .or_default()
+            .insert(*id, diff.clone());
           self.players_diff.insert(*id, diff);
         }
       }
     }
 
-    if !self.players_diff.is_empty() {
-      network_bus.add_global_package(Kind::UpdatePlayers(UpdatePlayersMap {
-        items: self.players_diff.clone(),
-      }))
+    for ((world, area), items) in areas_diff {
+      network_bus.add_area_package(world, area, Kind::UpdatePlayers(UpdatePlayersMap { items }));
     }
   }
 
@@ -121,12 +171,16 @@ impl PlayersManager {
     network_bus: &mut NetworkBus,
     event_bus: &mut EventBus,
   ) {
-    let players_clone = &self.players.clone();
-
     for (_, effects) in self.effects.iter_mut() {
       effects.retain(|_, effect_w| {
         if effect_w.effect().to_remove {
-          effect_w.disable(self.players.get_mut(&effect_w.effect().target_id).unwrap());
+          if let Some(hero) = self.players.get(&effect_w.effect().target_id) {
+            network_bus.add_area_package(
+              hero.player().world.clone(),
+              hero.player().area,
+              Kind::RemoveEffect(effect_w.effect().pack_removed()),
+            );
+          }
           false
         } else {
           true
@@ -134,54 +188,87 @@ impl PlayersManager {
       });
     }
 
+    // Modifiers and immortality are collected fresh every tick, so nothing can leak
+    for (id, hero) in self.players.iter_mut() {
+      let mut modifiers = hero.modifiers();
+      let immortal = hero.immortal();
+      let mut abilities_disabled = false;
+      if let Some(effects) = self.effects.get(id) {
+        for effect in effects.values() {
+          modifiers.extend(effect.modifiers());
+          abilities_disabled |= effect.disables_abilities();
+        }
+      }
+      let player = hero.player_mut();
+      player.modifiers = modifiers;
+      player.abilities_disabled = abilities_disabled;
+      player.immortal = immortal;
+      player.recompute_stats();
+    }
+
+    for world in worlds.values_mut() {
+      for area in world.areas.iter_mut() {
+        area.refresh_players(|id| self.players.get(id).map(|hero| hero.player()));
+      }
+    }
+
     for (id, hero) in self.players.iter_mut() {
       let player = hero.player();
-      if let Some(worlds) = worlds.get_mut(&player.world) {
-        if let Some(area) = worlds.areas.get_mut(player.area as usize) {
+      if let Some(world) = worlds.get(&player.world) {
+        if let Some(area) = world.areas.get(player.area as usize) {
           let mut update_player_props = PlayerUpdateProps {
             time_fix: update_props.time_fix,
             delta: update_props.delta,
-            players: area.get_players_vec(&players_clone),
+            players: &area.players,
+            grid: &area.player_grid,
             event_bus,
           };
           hero.update(&mut update_player_props);
           let boundary = area.as_boundary_player();
-          hero.collide(boundary);
+          hero.collide(boundary, &area.obstacles);
+
+          let regions = area.regions_at(hero.player());
+          let victory = RegionKind::Victory.flag();
+          if regions & victory != 0 && hero.player().regions & victory == 0 {
+            self.victories.push(*id);
+          }
+          hero.player_mut().regions = regions;
           if let Some(client) = network_bus.direct_clients.get_mut(id) {
             hero.input(&mut client.input);
+            client.processed_sequence = client.input.sequence;
           }
         }
       }
     }
 
-    event_bus.process_players_events(self);
+    event_bus.process_players_events(self, network_bus);
   }
 
-  pub fn add_player_effect(&mut self, effect: &mut PlayerEffectWrapper) {
-    if !self.has_player_effect(effect.effect_id(), effect.effect().target_id) {
-      if let Some(player) = self.players.get_mut(&effect.effect().target_id) {
-        if let Some(effects) = self.effects.get_mut(&(effect.effect().target_id as i64)) {
-          effect.enable(player);
-          effects.insert(effect.effect_id(), effect.clone());
-        } else {
-          let mut effects: HashMap<u64, PlayerEffectWrapper> = HashMap::new();
-          effect.enable(player);
-          effects.insert(effect.effect_id(), effect.clone());
-          self.effects.insert(effect.effect().target_id, effects);
+  pub fn add_player_effect(&mut self, effect: &PlayerEffectWrapper, network_bus: &mut NetworkBus) {
+    let target_id = effect.effect().target_id;
+    if let Some(player) = self.players.get(&target_id) {
+      let effects = self.effects.entry(target_id).or_default();
+      let package = match effects.get_mut(&effect.effect().key()) {
+        Some(current) => {
+          let before = current.effect().pack();
+          if current.effect_mut().reapply(effect.effect()) {
+            *current = effect.clone();
+          }
+          let after = current.effect().pack();
+          before.changed(&after).then(|| Kind::UpdateEffect(after))
         }
+        None => {
+          effects.insert(effect.effect().key(), effect.clone());
+          Some(Kind::AddEffect(effect.effect().pack()))
+        }
+      };
+      if let Some(package) = package {
+        let player = player.player();
+        network_bus.add_area_package(player.world.clone(), player.area, package);
       }
     }
   }
 
-  pub fn has_player_effect(&mut self, self_id: u64, target_id: i64) -> bool {
-    if let Some(effects) = self.effects.get(&target_id) {
-      if let Some(_) = effects.get(&self_id) {
-        return true;
-      }
-    }
-    false
-  }
-
   pub fn check_players_to_remove(&mut self) -> Vec<u32> {
     self.players_to_remove.clear();
 
@@ -212,8 +299,90 @@ impl PlayersManager {
     self.players.get(&id)
   }
 
-  pub(crate) fn pack_players(&self) -> HashMap<u32, PackedPlayer> {
-    let mut result = HashMap::new();
+  pub(crate) fn pack_area_players(&self, world: &str, area: u64) -> BTreeMap<u32, PackedPlayer> {
+    let mut result = BTreeMap::new();
+
+    for (id, hero) in self.players.iter() {
+      let player = hero.player();
+      if player.world == world && player.area == area {
+        result.insert(*id as u32, hero.pack());
+      }
+    }
+
+    result
+  }
+
+  // Everything a client needs to rebuild its area from scratch, culled to its view radius
+  pub(crate) fn pack_area_state(
+    &self,
+    world: &World,
+    player: &Player,
+    view_radius: Option<f64>,
+  ) -> Vec<Kind> {
+    let mut area = world.pack_area(player.area as usize);
+    let mut players = self.pack_area_players(&player.world, player.area);
+    if let (Some(radius), Some(me)) = (view_radius, players.get(&(player.id as u32)).cloned()) {
+      let view = View::around(&me, radius);
+      area.entities.retain(|_, entity| view.sees_entity(entity));
+      players.retain(|_, other| view.sees_player(&me, other));
+    }
+
+    let mut result = vec![Kind::AreaInit(area), Kind::Players(Players { players })];
+    for effect in self.pack_area_effects(&player.world, player.area) {
+      result.push(Kind::AddEffect(effect));
+    }
+    result
+  }
+
+  pub(crate) fn pack_area_effects(&self, world: &str, area: u64) -> Vec<PackedEffect> {
+    let mut result = Vec::new();
+
+    for (id, effects) in self.effects.iter() {
+      if let Some(hero) = self.players.get(id) {
+        let player = hero.player();
+        if player.world == world && player.area == area {
+          result.extend(effects.values().map(|effect| effect.effect().pack()));
+        }
+      }
+    }
+
+    result
+  }
+
+  pub(crate) fn pack_effects(&self) -> BTreeMap<(u32, u32, u32), PackedEffect> {
+    let mut result = BTreeMap::new();
+
+    for effects in self.effects.values() {
+      for effect in effects.values() {
+        let packed = effect.effect().pack();
+        result.insert((packed.player_id, packed.effect_type, packed.instance), packed);
+      }
+    }
+
+    result
+  }
+
+  pub(crate) fn roster(&self) -> Roster {
+    let mut players = BTreeMap::new();
+
+    for (id, hero) in self.players.iter() {
+      let player = hero.player();
+      players.insert(
+        *id as u32,
+        RosterPlayer {
+          name: player.name.clone(),
+          world: player.world.clone(),
+          area: player.area as u32,
+          died: player.downed,
+        },
+      );
+    }
+
+    Roster { players }
+  }
+
+  pub(crate) fn pack_players(&self) -> BTreeMap<u32, PackedPlayer> {
+    let mut result = BTreeMap::new();
 
     for (id, hero) in self.players.iter() {
       result.insert(*id as u32, hero.pack());
@@ -1,43 +1,53 @@
 use crate::bus::{EventBus, NetworkBus};
-use crate::config::Config;
+use crate::config::{Config, RawPortal};
 use crate::managers::player::PlayersManager;
 use crate::props::EngineProps;
 use crate::proto::package::Kind;
-use crate::proto::{
-  CloseEntities, Entities, PackedEntity, PartialEntity, Players, UpdateEntitiesMap,
-};
+use crate::resources::area::Area;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::player::Player;
 use crate::resources::world::World;
-use crate::resources::{distance, EffectUpdateProps, EntityUpdateProps, UpdateProps};
-use std::collections::HashMap;
+use crate::resources::{EffectUpdateProps, UpdateProps};
+use napi::{Error, Status};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::{BTreeMap, HashMap};
 
 pub struct WorldsManager {
   pub worlds: HashMap<String, World>,
-  pub new_entities: HashMap<u64, PackedEntity>,
-  pub old_entities: HashMap<u64, PackedEntity>,
-  pub entities_diff: HashMap<u32, PartialEntity>,
-  pub spawned_entities: HashMap<u32, PackedEntity>,
-  pub entities_to_remove: Vec<u32>,
+  pool: Option<ThreadPool>,
 }
 
-pub enum Change {
-  NextArea,
-  PrevArea,
-  NextWorld,
-  PrevWorld,
+struct AreaJob<'a> {
+  name: &'a String,
+  index: usize,
+  area: &'a mut Area,
+  heroes: HashMap<i64, &'a mut HeroWrapper>,
+  packages: Vec<Kind>,
 }
 
 impl WorldsManager {
-  pub fn new(props: &EngineProps) -> Self {
-    Self {
-      worlds: props.load_worlds().unwrap(),
-      new_entities: HashMap::new(),
-      old_entities: HashMap::new(),
-      entities_diff: HashMap::new(),
-      spawned_entities: HashMap::new(),
-      entities_to_remove: Vec::new(),
-    }
+  pub fn new(props: &EngineProps, seed: u64, config: &Config) -> Result<Self, Error> {
+    let pool = match props.threads() {
+      Some(1) | None => None,
+      Some(threads) => Some(
+        ThreadPoolBuilder::new()
+          .num_threads(threads as usize)
+          .build()
+          .map_err(|e| {
+            Error::new(
+              Status::GenericFailure,
+              "Attempt to create thread pool ".to_string() + e.to_string().as_str(),
+            )
+          })?,
+      ),
+    };
+    let mut manager = Self {
+      worlds: props.load_worlds(seed)?,
+      pool,
+    };
+    manager.prepare_portals(&config.worlds);
+    Ok(manager)
   }
 
   pub fn update(
@@ -47,160 +57,178 @@ impl WorldsManager {
     network_bus: &mut NetworkBus,
     event_bus: &mut EventBus,
   ) {
-    let players_clone = &mut players_manager.players.clone();
-    let players = &mut players_manager.players;
-    for (name, world) in self.worlds.iter_mut() {
-      for (index, area) in world.areas.iter_mut().enumerate() {
-        self.old_entities = area.get_packed_entities();
-        event_bus.entities_to_spawn.clear();
-        let boundary = area.as_boundary();
-
-        let mut entity_update = EntityUpdateProps {
-          delta: props.delta,
-          time_fix: props.time_fix,
-          players: area.get_players_vec(&players_clone),
-          event_bus,
-        };
+    self.update_effects(props, players_manager);
 
-        for (_, effects) in players_manager.effects.iter_mut() {
-          for (_, effect) in effects.iter_mut() {
-            let target_id = effect.effect().target_id.clone();
-            let caster_id = effect.effect().caster_id.clone();
+    let mut heroes: HashMap<(String, u64), HashMap<i64, &mut HeroWrapper>> = HashMap::new();
+    for (id, hero) in players_manager.players.iter_mut() {
+      let key = (hero.player().world.clone(), hero.player().area);
+      heroes.entry(key).or_default().insert(*id, hero);
+    }
 
-              if let Some(target) = players.get_mut(&target_id) {
-                if let Some(caster) = area.entities.get_mut(&caster_id) {
-                  effect.update(&mut EffectUpdateProps {
-                    delta: props.delta,
-                    time_fix: props.time_fix,
-                    caster,
-                    target,
-                    boundary,
-                  });
-                }
-              }
-          }
+    let mut jobs = Vec::new();
+    for (name, world) in self.worlds.iter_mut() {
+      for (index, area) in world.areas.iter_mut().enumerate() {
+        if area.players_id.is_empty() {
+          continue;
         }
-
-        area.entities.retain(|id, entity| {
-          if entity.entity().to_remove {
-            self.entities_to_remove.push(*id as u32);
-            false
-          } else {
-            true
-          }
+        jobs.push(AreaJob {
+          name,
+          index,
+          area,
+          heroes: heroes.remove(&(name.clone(), index as u64)).unwrap_or_default(),
+          packages: Vec::new(),
         });
+      }
+    }
+    jobs.sort_by(|a, b| (a.name, a.index).cmp(&(b.name, b.index)));
+
+    let run = |job: &mut AreaJob| {
+      job.packages = job.area.update(props, &mut job.heroes);
+    };
+    match &self.pool {
+      Some(pool) => pool.install(|| jobs.par_iter_mut().for_each(run)),
+      None => jobs.iter_mut().for_each(run),
+    }
 
-        for (_, entity) in area.entities.iter_mut() {
-          entity.update(&mut entity_update);
-        }
-
-        for (_, entity) in area.entities.iter_mut() {
-          for id in &area.players_id {
-            if let Some(player) = players.get_mut(&id) {
-              entity.interact(player);
-            }
-          }
-        }
-
-        for first_id in &area.players_id {
-          for second_id in &area.players_id {
-            if first_id == second_id {
-              continue;
-            }
-
-            let can_rescue = {
-              let first_player = players.get(&first_id).unwrap().player();
-              let second_player = players.get(&second_id).unwrap().player();
-              second_player.downed
-                && distance(
-                  second_player.pos.x - first_player.pos.x,
-                  second_player.pos.y - first_player.pos.y,
-                ) <= first_player.radius + second_player.radius
-            };
+    for job in jobs {
+      for package in job.packages {
+        network_bus.add_area_package(job.name.clone(), job.index as u64, package);
+      }
+      event_bus
+        .players_events
+        .append(&mut job.area.events.players_events);
+    }
+  }
 
-            if can_rescue {
-              if let Some(second_player) = players.get_mut(&second_id) {
-                second_player.res();
+  fn update_effects(&mut self, props: &UpdateProps, players_manager: &mut PlayersManager) {
+    let players = &mut players_manager.players;
+    for (_, effects) in players_manager.effects.iter_mut() {
+      for (_, effect) in effects.iter_mut() {
+        effect.effect_mut().tick(props.delta);
+        let target_id = effect.effect().target_id;
+        let caster_id = effect.effect().caster_id;
+
+        if let Some(target) = players.get_mut(&target_id) {
+          let (world_name, area_index) = (target.player().world.clone(), target.player().area);
+          let world = self.worlds.get(&world_name);
+          let area = world.and_then(|world| world.areas.get(area_index as usize));
+          if let Some(area) = area {
+            // Entity ids are only unique within an area, so a bound caster is looked up there only
+            let caster = match &effect.effect().caster_area {
+              Some(caster_area) => {
+                let caster = area.entities.get(&caster_id);
+                if *caster_area != (world_name, area_index) || caster.is_none() {
+                  effect.effect_mut().to_remove = true;
+                  continue;
+                }
+                caster
               }
-            }
+              None => None,
+            };
+            effect.update(&mut EffectUpdateProps {
+              delta: props.delta,
+              time_fix: props.time_fix,
+              caster,
+              target,
+              boundary: area.as_boundary(),
+            });
           }
         }
+      }
+    }
+  }
 
-        for entity in event_bus.entities_to_spawn.iter() {
-          let id = area.add_entity(entity.clone());
-          self.spawned_entities.insert(id as u32, entity.pack());
-        }
-
-        self.new_entities = area.get_packed_entities();
+  fn prepare_portals(&mut self, world_names: &Vec<String>) {
+    let mut first_area_heights: HashMap<String, f64> = HashMap::new();
+    for (name, world) in self.worlds.iter() {
+      if let Some(area) = world.areas.first() {
+        first_area_heights.insert(name.clone(), area.raw_area.h);
+      }
+    }
 
-        for (id, entity) in self.new_entities.iter() {
-          if let Some(old_entity) = self.old_entities.get(&id) {
-            let (diff, changed) = old_entity.diff(&entity);
-            if changed {
-              self.entities_diff.insert(*id as u32, diff);
-            }
-          }
+    for (name, world) in self.worlds.iter_mut() {
+      let widths: Vec<f64> = world.areas.iter().map(|area| area.raw_area.w).collect();
+      for (index, area) in world.areas.iter_mut().enumerate() {
+        if !area.raw_area.portals.is_empty() {
+          continue;
         }
-
-        if !self.spawned_entities.is_empty() {
-          let package = Kind::NewEntities(Entities {
-            entities: self.spawned_entities.clone(),
+        let w = area.raw_area.w;
+        let h = area.raw_area.h;
+
+        if index + 1 < widths.len() {
+          area.raw_area.portals.push(RawPortal {
+            x: w + 8.0 * 32.0,
+            y: 0.0,
+            w: 2.0 * 32.0,
+            h,
+            world: None,
+            area: index as u64 + 1,
+            spawn_x: Some(-7.0 * 32.0),
+            spawn_y: None,
           });
-          network_bus.add_area_package(name.clone(), index as u64, package.clone());
-          self.spawned_entities.clear();
         }
 
-        if !self.entities_to_remove.is_empty() {
-          let package = Kind::CloseEntities(CloseEntities {
-            ids: self.entities_to_remove.clone(),
+        if index > 0 {
+          area.raw_area.portals.push(RawPortal {
+            x: -10.0 * 32.0,
+            y: 0.0,
+            w: 2.0 * 32.0,
+            h,
+            world: None,
+            area: index as u64 - 1,
+            spawn_x: Some(widths[index - 1] + 7.0 * 32.0),
+            spawn_y: None,
           });
-          network_bus.add_area_package(name.clone(), index as u64, package.clone());
-          self.entities_to_remove.clear();
         }
 
-        if !self.entities_diff.is_empty() {
-          let package = Kind::UpdateEntities(UpdateEntitiesMap {
-            items: self.entities_diff.clone(),
-          });
-          network_bus.add_area_package(name.clone(), index as u64, package.clone());
-          self.entities_diff.clear();
+        if index == 0 && !world_names.is_empty() {
+          let next_world = WorldsManager::get_next_world(world_names, name);
+          if let Some(next_h) = first_area_heights.get(&next_world) {
+            area.raw_area.portals.push(RawPortal {
+              x: -10.0 * 32.0,
+              y: 0.0,
+              w: 10.0 * 32.0,
+              h: 2.0 * 32.0,
+              world: Some(next_world.clone()),
+              area: 0,
+              spawn_x: None,
+              spawn_y: Some(next_h - 3.0 * 32.0),
+            });
+          }
+
+          let prev_world = WorldsManager::get_prev_world(world_names, name);
+          if first_area_heights.contains_key(&prev_world) {
+            area.raw_area.portals.push(RawPortal {
+              x: -10.0 * 32.0,
+              y: h - 2.0 * 32.0,
+              w: 10.0 * 32.0,
+              h: 2.0 * 32.0,
+              world: Some(prev_world),
+              area: 0,
+              spawn_x: None,
+              spawn_y: Some(3.0 * 32.0),
+            });
+          }
         }
       }
     }
   }
 
-  pub fn prepare_warps(&mut self, players: &HashMap<i64, HeroWrapper>) -> HashMap<i64, Change> {
-    let mut changes: HashMap<i64, Change> = HashMap::new();
+  pub fn prepare_warps(&self, players: &BTreeMap<i64, HeroWrapper>) -> BTreeMap<i64, RawPortal> {
+    let mut changes: BTreeMap<i64, RawPortal> = BTreeMap::new();
 
     for (id, hero) in players.iter() {
       let player = hero.player();
+      if player.in_portal {
+        continue;
+      }
       if let Some(world) = self.worlds.get(&player.world) {
         if let Some(area) = world.areas.get(player.area as usize) {
-          if player.pos.x + player.radius > area.raw_area.w + 8.0 * 32.0
-            && self.get_next_area(&player)
-          {
-            changes.insert(*id, Change::NextArea);
-          }
-
-          if player.area > 0
-            && player.pos.x - player.radius < -8.0 * 32.0
-            && self.get_prev_area(&player)
-          {
-            changes.insert(*id, Change::PrevArea);
-          }
-
-          if player.area == 0
-            && player.pos.x - player.radius < 0.0
-            && player.pos.y - player.radius < 2.0 * 32.0
-          {
-            changes.insert(*id, Change::NextWorld);
-          }
-
-          if player.area == 0
-            && player.pos.x - player.radius < 0.0
-            && player.pos.y + player.radius > area.raw_area.h - 2.0 * 32.0
-          {
-            changes.insert(*id, Change::PrevWorld);
+          for portal in area.raw_area.portals.iter() {
+            if WorldsManager::touches_portal(player, portal) && self.portal_target_exists(portal, &player.world) {
+              changes.insert(*id, portal.clone());
+              break;
+            }
           }
         }
       }
@@ -209,86 +237,86 @@ impl WorldsManager {
     changes
   }
 
-  pub fn process_warps(
-    &mut self,
-    players_manager: &mut PlayersManager,
-    config: &Config,
-    network_bus: &mut NetworkBus,
-  ) {
+  fn touches_portal(player: &Player, portal: &RawPortal) -> bool {
+    player.pos.x + player.radius > portal.x
+      && player.pos.x - player.radius < portal.x + portal.w
+      && player.pos.y + player.radius > portal.y
+      && player.pos.y - player.radius < portal.y + portal.h
+  }
+
+  fn touches_any_portal(&self, player: &Player) -> bool {
+    match self.worlds.get(&player.world) {
+      Some(world) => match world.areas.get(player.area as usize) {
+        Some(area) => area
+          .raw_area
+          .portals
+          .iter()
+          .any(|portal| WorldsManager::touches_portal(player, portal)),
+        None => false,
+      },
+      None => false,
+    }
+  }
+
+  fn portal_target_exists(&self, portal: &RawPortal, current_world: &String) -> bool {
+    let world_name = portal.world.as_ref().unwrap_or(current_world);
+    match self.worlds.get(world_name) {
+      Some(world) => (portal.area as usize) < world.areas.len(),
+      None => false,
+    }
+  }
+
+  pub fn process_warps(&mut self, players_manager: &mut PlayersManager, network_bus: &mut NetworkBus) {
+    for hero in players_manager.players.values_mut() {
+      let player = hero.player_mut();
+      if player.in_portal && !self.touches_any_portal(player) {
+        player.in_portal = false;
+      }
+    }
+
     let warps = self.prepare_warps(&players_manager.players);
-    for (id, change) in &warps {
+    for (id, portal) in &warps {
       if let Some(hero) = players_manager.players.get_mut(&id) {
         let player = hero.player_mut();
-        match change {
-          Change::NextArea => {
-            if let Some(world) = self.worlds.get_mut(&player.world) {
-              if let Some(area) = world.areas.get_mut(player.area as usize) {
-                area.leave(player.id);
-              }
-              player.area += 1;
-              player.pos.x = -8.0 * 32.0 + player.radius;
-              let next_area = world.areas.get_mut(player.area as usize).unwrap();
-              next_area.join(player.id);
-              let area_init_package = Kind::AreaInit(world.pack_area(player.area as usize));
-              network_bus.add_direct_package(*id, area_init_package);
-              let players_package = Kind::Players(Players {
-                players: players_manager.pack_players(),
-              });
-              network_bus.add_direct_package(*id, players_package);
-            }
-          }
-          Change::PrevArea => {
-            if let Some(world) = self.worlds.get_mut(&player.world) {
-              if let Some(area) = world.areas.get_mut(player.area as usize) {
-                area.leave(player.id);
-              }
-              player.area -= 1;
-              let prev_area = world.areas.get_mut(player.area as usize).unwrap();
-              prev_area.join(player.id);
+        if let Some(prev_world) = self.worlds.get_mut(&player.world) {
+          prev_world.leave(&player);
+        }
+        network_bus.add_area_package(
+          player.world.clone(),
+          player.area,
+          Kind::ClosePlayer(player.id),
+        );
+
+        if let Some(world_name) = &portal.world {
+          player.world = world_name.clone();
+        }
+        player.area = portal.area;
+        player.in_portal = true;
+        if let Some(x) = portal.spawn_x {
+          player.pos.x = x;
+        }
+        if let Some(y) = portal.spawn_y {
+          player.pos.y = y;
+        }
 
-              player.pos.x = prev_area.raw_area.w + 8.0 * 32.0 - player.radius;
-              let area_init_package = Kind::AreaInit(world.pack_area(player.area as usize));
-              network_bus.add_direct_package(*id, area_init_package);
-              let players_package = Kind::Players(Players {
-                players: players_manager.pack_players(),
-              });
-              network_bus.add_direct_package(*id, players_package);
-            }
-          }
-          Change::NextWorld => {
-            if let Some(prev_world) = self.worlds.get_mut(&player.world) {
-              prev_world.leave(&player);
-            }
-            let next_world_name = WorldsManager::get_next_world(&config.worlds, &player.world);
-            let next_world = self.worlds.get_mut(&next_world_name).unwrap();
-            let area = next_world.areas.get_mut(0).unwrap();
-            player.world = next_world_name;
-            player.pos.y = area.raw_area.h - player.radius - 2.0 * 32.0;
-            next_world.join(&player);
-            let area_init_package = Kind::AreaInit(next_world.pack_area(player.area as usize));
-            network_bus.add_direct_package(*id, area_init_package);
-            let players_package = Kind::Players(Players {
-              players: players_manager.pack_players(),
-            });
-            network_bus.add_direct_package(*id, players_package);
+        if let Some(next_world) = self.worlds.get_mut(&player.world) {
+          next_world.join(&player);
+          // The flags of the previous area must not leak into the next tick
+          if let Some(area) = next_world.areas.get(player.area as usize) {
+            player.regions = area.regions_at(player);
           }
-          Change::PrevWorld => {
-            if let Some(prev_world) = self.worlds.get_mut(&player.world) {
-              prev_world.leave(&player);
+          let world_name = player.world.clone();
+          let area = player.area;
+          network_bus.add_area_package(world_name.clone(), area, Kind::NewPlayer(hero.pack()));
+          // The players already there never saw the effects the newcomer brings along
+          if let Some(effects) = players_manager.effects.get(id) {
+            for effect in effects.values() {
+              let package = Kind::AddEffect(effect.effect().pack());
+              network_bus.add_area_package(world_name.clone(), area, package);
             }
-            let prev_world_name = WorldsManager::get_prev_world(&config.worlds, &player.world);
-            let prev_world = self.worlds.get_mut(&prev_world_name).unwrap();
-            player.world = prev_world_name;
-            player.pos.y = player.radius + 2.0 * 32.0;
-            prev_world.join(&player);
-            let area_init_package = Kind::AreaInit(prev_world.pack_area(player.area as usize));
-            network_bus.add_direct_package(*id, area_init_package);
-            let players_package = Kind::Players(Players {
-              players: players_manager.pack_players(),
-            });
-            network_bus.add_direct_package(*id, players_package);
           }
-        };
+          network_bus.resync(*id);
+        }
       }
     }
   }
@@ -310,22 +338,4 @@ impl WorldsManager {
       _ => world_names.get(world_names.len() - 1).unwrap().clone(),
     }
   }
-
-  pub fn get_next_area(&self, player: &Player) -> bool {
-    if let Some(world) = self.worlds.get(&player.world) {
-      if let Some(_) = world.areas.get(player.area as usize + 1) {
-        return true;
-      }
-    }
-    false
-  }
-
-  pub fn get_prev_area(&self, player: &Player) -> bool {
-    if let Some(world) = self.worlds.get(&player.world) {
-      if let Some(_) = world.areas.get(player.area as usize - 1) {
-        return true;
-      }
-    }
-    false
-  }
 }
@@ -0,0 +1,30 @@
+use napi::{Error, Status};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Snapshots and recordings share one compact binary format, lz4 compressed bincode
+pub fn encode<T: Serialize>(value: &T, name: &str) -> Result<Vec<u8>, Error> {
+  match bincode::serde::encode_to_vec(value, bincode::config::standard()) {
+    Ok(buffer) => Ok(lz4_flex::compress_prepend_size(&buffer)),
+    Err(e) => Err(Error::new(
+      Status::GenericFailure,
+      format!("Attempt to write {} ", name) + e.to_string().as_str(),
+    )),
+  }
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8], name: &str) -> Result<T, Error> {
+  let buffer = lz4_flex::decompress_size_prepended(bytes).map_err(|e| {
+    Error::new(
+      Status::InvalidArg,
+      format!("Attempt to read invalid {} ", name) + e.to_string().as_str(),
+    )
+  })?;
+  match bincode::serde::decode_from_slice(&buffer, bincode::config::standard()) {
+    Ok((value, _)) => Ok(value),
+    Err(e) => Err(Error::new(
+      Status::InvalidArg,
+      format!("Attempt to read invalid {} ", name) + e.to_string().as_str(),
+    )),
+  }
+}
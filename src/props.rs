@@ -10,13 +10,30 @@ use crate::config::Config;
 pub struct EngineProps {
   config: String,
   worlds: Vec<String>,
+  threads: Option<u32>,
 }
 
 #[napi]
 impl EngineProps {
   #[napi(constructor)]
-  pub fn new(config: String, worlds: Vec<String>) -> Self {
-    Self { config, worlds }
+  pub fn new(config: String, worlds: Vec<String>, threads: Option<u32>) -> Self {
+    Self {
+      config,
+      worlds,
+      threads,
+    }
+  }
+
+  pub fn raw_config(&self) -> &String {
+    &self.config
+  }
+
+  pub fn raw_worlds(&self) -> &Vec<String> {
+    &self.worlds
+  }
+
+  pub fn threads(&self) -> Option<u32> {
+    self.threads
   }
 
   pub fn load_config(&self) -> Result<Config, Error> {
@@ -29,13 +46,13 @@ impl EngineProps {
     }
   }
 
-  pub fn load_worlds(&self) -> Result<HashMap<String, World>, Error> {
+  pub fn load_worlds(&self, seed: u64) -> Result<HashMap<String, World>, Error> {
     let mut result: HashMap<String, World> = HashMap::new();
     for world in self.worlds.iter() {
       match serde_json::from_str::<RawWorld>(&world) {
         Ok(e) => {
           let raw = &e;
-          result.insert(raw.name.clone(), World::new(raw.clone()))
+          result.insert(raw.name.clone(), World::new(raw.clone(), seed))
         }
         Err(e) => {
           return Err(Error::new(
@@ -0,0 +1,261 @@
+use crate::bus::Client;
+use crate::compression::Codec;
+use crate::managers::baseline::BaselinesManager;
+use crate::managers::interest::ViewState;
+use crate::persist;
+use crate::proto::{Package, Packages};
+use crate::resources::utils::input::Input;
+use napi::{Error, Status};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+  pub seed: u64,
+  pub config: String,
+  pub worlds: Vec<String>,
+  pub remove_dead_players: bool,
+  // A recording started mid-session replays from the state it started in
+  pub snapshot: Option<Vec<u8>>,
+  pub dictionary: Option<Vec<u8>>,
+  pub clients: Vec<RecordedClient>,
+  // Packages queued for areas and what clients acknowledged when the recording started
+  pub area_packages: Vec<((String, u64), Packages)>,
+  pub baselines: BaselinesManager,
+  pub accumulator: f64,
+  pub send_elapsed: f64,
+  pub records: Vec<Record>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Record {
+  pub tick: u64,
+  pub kind: record::Kind,
+}
+
+pub mod record {
+  use super::{
+    RecordedAck, RecordedChat, RecordedInput, RecordedJoin, RecordedSendRate, RecordedUpdate,
+    RecordedViewRadius,
+  };
+  use serde::{Deserialize, Serialize};
+
+  #[derive(Clone, Serialize, Deserialize)]
+  pub enum Kind {
+    Update(RecordedUpdate),
+    Input(RecordedInput),
+    Join(RecordedJoin),
+    Leave(i64),
+    Chat(RecordedChat),
+    PlayerDeathHandler(bool),
+    Ack(RecordedAck),
+    ViewRadius(RecordedViewRadius),
+    SendRate(RecordedSendRate),
+    Restore(Vec<u8>),
+    Dictionary(Vec<u8>),
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedUpdate {
+  pub delta: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedJoin {
+  pub id: i64,
+  pub name: String,
+  pub hero: Option<String>,
+  pub compression: Option<String>,
+  pub view_radius: Option<f64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedAck {
+  pub id: i64,
+  pub tick: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedViewRadius {
+  pub id: i64,
+  pub radius: Option<f64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedSendRate {
+  pub id: i64,
+  pub rate: Option<f64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedClient {
+  pub id: i64,
+  pub codec: Codec,
+  pub view_radius: Option<f64>,
+  pub send_interval: f64,
+  pub since_send: f64,
+  pub input: RecordedInput,
+  pub processed_sequence: u32,
+  pub packages: Packages,
+  pub view: ViewState,
+  pub pending_area: Vec<Package>,
+  pub resync: bool,
+}
+
+impl RecordedClient {
+  pub fn new(id: i64, client: &Client) -> Self {
+    Self {
+      id,
+      codec: client.codec,
+      view_radius: client.view_radius,
+      send_interval: client.send_interval,
+      since_send: client.since_send,
+      input: RecordedInput::new(id, &client.input),
+      processed_sequence: client.processed_sequence,
+      packages: client.packages.clone(),
+      view: client.view.clone(),
+      pending_area: client.pending_area.clone(),
+      resync: client.resync,
+    }
+  }
+
+  pub fn apply(&self, client: &mut Client) {
+    client.codec = self.codec;
+    client.view_radius = self.view_radius;
+    client.send_interval = self.send_interval;
+    client.since_send = self.since_send;
+    client.input = self.input.to_input();
+    client.processed_sequence = self.processed_sequence;
+    client.packages = self.packages.clone();
+    client.view = self.view.clone();
+    client.pending_area = self.pending_area.clone();
+    client.resync = self.resync;
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedChat {
+  pub id: u32,
+  pub content: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedInput {
+  pub id: i64,
+  pub left: bool,
+  pub right: bool,
+  pub up: bool,
+  pub down: bool,
+  pub shift: bool,
+  pub mouse_enable: bool,
+  pub mouse_pos_x: f64,
+  pub mouse_pos_y: f64,
+  pub first_ability: bool,
+  pub second_ability: bool,
+  pub sequence: u32,
+}
+
+impl RecordedInput {
+  pub fn new(id: i64, input: &Input) -> Self {
+    Self {
+      id,
+      left: input.left,
+      right: input.right,
+      up: input.up,
+      down: input.down,
+      shift: input.shift,
+      mouse_enable: input.mouse_enable,
+      mouse_pos_x: input.mouse_pos_x,
+      mouse_pos_y: input.mouse_pos_y,
+      first_ability: input.first_ability,
+      second_ability: input.second_ability,
+      sequence: input.sequence,
+    }
+  }
+
+  pub fn to_input(&self) -> Input {
+    let mut input = Input::new();
+    input.left = self.left;
+    input.right = self.right;
+    input.up = self.up;
+    input.down = self.down;
+    input.shift = self.shift;
+    input.mouse_enable = self.mouse_enable;
+    input.mouse_pos_x = self.mouse_pos_x;
+    input.mouse_pos_y = self.mouse_pos_y;
+    input.first_ability = self.first_ability;
+    input.second_ability = self.second_ability;
+    input.sequence = self.sequence;
+    input
+  }
+}
+
+pub struct Recorder {
+  recording: Recording,
+  flushed: bool,
+}
+
+impl Recorder {
+  pub fn new(recording: Recording) -> Self {
+    Self {
+      recording,
+      flushed: false,
+    }
+  }
+
+  pub fn record(&mut self, tick: u64, kind: record::Kind) {
+    self.recording.records.push(Record { tick, kind });
+  }
+
+  pub fn export(&self) -> Result<Vec<u8>, Error> {
+    if self.flushed {
+      return Err(Error::new(
+        Status::GenericFailure,
+        "Recording was already flushed in chunks".to_string(),
+      ));
+    }
+    persist::encode(&self.recording, "recording")
+  }
+
+  // Hands out the records since the last flush, the first chunk is a recording of its own
+  pub fn flush(&mut self) -> Result<Vec<u8>, Error> {
+    if self.flushed {
+      return persist::encode(&std::mem::take(&mut self.recording.records), "recording chunk");
+    }
+    let bytes = persist::encode(&self.recording, "recording")?;
+    self.recording.records.clear();
+    self.flushed = true;
+    Ok(bytes)
+  }
+}
+
+impl Recording {
+  pub fn import(bytes: &[u8]) -> Result<Self, Error> {
+    persist::decode(bytes, "recording")
+  }
+
+  pub fn import_chunk(bytes: &[u8]) -> Result<Vec<Record>, Error> {
+    persist::decode(bytes, "recording chunk")
+  }
+}
+
+pub struct Replay {
+  records: VecDeque<Record>,
+}
+
+impl Replay {
+  pub fn new(records: Vec<Record>) -> Self {
+    Self {
+      records: records.into(),
+    }
+  }
+
+  pub fn extend(&mut self, records: Vec<Record>) {
+    self.records.extend(records);
+  }
+
+  pub fn next_record(&mut self) -> Option<record::Kind> {
+    self.records.pop_front().map(|record| record.kind)
+  }
+}
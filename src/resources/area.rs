@@ -1,31 +1,93 @@
-use crate::config::RawArea;
-use crate::proto::PackedEntity;
+use crate::bus::EventBus;
+use crate::config::{RawArea, RawRegion, RegionKind};
+use crate::proto::package::Kind;
+use crate::proto::{CloseEntities, Entities, PackedEntity, UpdateEntitiesMap};
 use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::assets::hero::HeroWrapper;
-use crate::resources::player::Player;
-use crate::resources::{random, AdditionalEntityProps, Boundary, EntityProps};
-use std::collections::HashMap;
+use crate::resources::grid::{players_in_radius, SpatialGrid};
+use crate::resources::obstacle::Obstacle;
+use crate::resources::player::{Player, PlayerView};
+use crate::resources::utils::vector::Vector;
+use crate::resources::{
+  distance, random, swap_random, AdditionalEntityProps, Boundary, EntityProps, EntityUpdateProps,
+  UpdateProps,
+};
+use rand_chacha::ChaCha12Rng;
+use rand::SeedableRng;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct Area {
-  pub entities: HashMap<u64, EntityWrapper>,
+  pub entities: BTreeMap<u64, EntityWrapper>,
   pub players_id: Vec<i64>,
   pub raw_area: RawArea,
   pub next_id: u64,
+  pub obstacles: Vec<Obstacle>,
+  pub entity_grid: SpatialGrid<u64>,
+  pub players: Vec<PlayerView>,
+  pub player_grid: SpatialGrid<usize>,
+  pub events: EventBus,
+  rng: ChaCha12Rng,
 }
 
 impl Area {
-  pub fn new(raw_area: RawArea) -> Self {
+  pub fn new(mut raw_area: RawArea, seed: u64) -> Self {
+    if !raw_area.regions.iter().any(|region| region.kind == RegionKind::Safe) {
+      raw_area.regions.push(RawRegion {
+        kind: RegionKind::Safe,
+        x: -10.0 * 32.0,
+        y: 0.0,
+        w: 10.0 * 32.0,
+        h: raw_area.h,
+      });
+      raw_area.regions.push(RawRegion {
+        kind: RegionKind::Safe,
+        x: raw_area.w,
+        y: 0.0,
+        w: 10.0 * 32.0,
+        h: raw_area.h,
+      });
+    }
+    let obstacles = raw_area.obstacles.iter().map(Obstacle::new).collect();
     Self {
-      entities: HashMap::new(),
+      entities: BTreeMap::new(),
       players_id: Vec::new(),
       raw_area,
       next_id: 0,
+      obstacles,
+      entity_grid: SpatialGrid::new(),
+      players: Vec::new(),
+      player_grid: SpatialGrid::new(),
+      events: EventBus::new(),
+      rng: ChaCha12Rng::seed_from_u64(seed),
     }
   }
 
+  pub fn swap_random(&mut self) {
+    swap_random(&mut self.rng);
+  }
+
+  pub fn rng(&self) -> &ChaCha12Rng {
+    &self.rng
+  }
+
+  pub fn restore(
+    &mut self,
+    entities: BTreeMap<u64, EntityWrapper>,
+    players_id: Vec<i64>,
+    next_id: u64,
+    rng: ChaCha12Rng,
+  ) {
+    self.entities = entities;
+    self.players_id = players_id;
+    self.next_id = next_id;
+    self.rng = rng;
+  }
+
   pub fn join(&mut self, id: i64) {
     if self.players_id.len() == 0 {
+      self.swap_random();
       self.init();
+      self.swap_random();
     }
     self.players_id.push(id);
   }
@@ -37,8 +99,8 @@ impl Area {
     }
   }
 
-  pub fn get_packed_entities(&self) -> HashMap<u64, PackedEntity> {
-    let mut packed_entities: HashMap<u64, PackedEntity> = HashMap::new();
+  pub fn get_packed_entities(&self) -> BTreeMap<u64, PackedEntity> {
+    let mut packed_entities: BTreeMap<u64, PackedEntity> = BTreeMap::new();
 
     for (id, entity) in self.entities.iter() {
       packed_entities.insert(*id, entity.pack());
@@ -84,8 +146,10 @@ impl Area {
             inverse: false,
           };
 
-          if let Ok(entity) = EntityWrapper::new(type_name.as_str(), &mut props.clone(), additional)
+          if let Ok(mut entity) =
+            EntityWrapper::new(type_name.as_str(), &mut props.clone(), additional)
           {
+            entity.collide_obstacles(&self.obstacles);
             self.entities.insert(self.next_id, entity);
             self.next_id += 1;
           }
@@ -105,16 +169,132 @@ impl Area {
     self.next_id
   }
 
-  pub fn get_players_vec<'a>(&self, players: &'a HashMap<i64, HeroWrapper>) -> Vec<&'a Player> {
-    let mut arr = Vec::new();
+  pub fn refresh_players<'a>(&mut self, lookup: impl Fn(&i64) -> Option<&'a Player>) {
+    self.players.clear();
+    self.player_grid.clear();
 
     for id in &self.players_id {
-      if let Some(player) = players.get(id) {
-        arr.push(player.player());
+      if let Some(player) = lookup(id) {
+        self.player_grid.insert(self.players.len(), player.pos.x, player.pos.y, player.radius);
+        self.players.push(player.view());
+      }
+    }
+  }
+
+  pub fn update(
+    &mut self,
+    props: &UpdateProps,
+    heroes: &mut HashMap<i64, &mut HeroWrapper>,
+  ) -> Vec<Kind> {
+    self.swap_random();
+    let old_entities = self.get_packed_entities();
+    let mut packages = Vec::new();
+
+    let mut entities_to_remove = Vec::new();
+    self.entities.retain(|id, entity| {
+      if entity.entity().to_remove {
+        entities_to_remove.push(*id as u32);
+        false
+      } else {
+        true
+      }
+    });
+
+    self.refresh_players(|id| heroes.get(id).map(|hero| hero.player()));
+    let mut entity_update = EntityUpdateProps {
+      delta: props.delta,
+      time_fix: props.time_fix,
+      players: &self.players,
+      grid: &self.player_grid,
+      event_bus: &mut self.events,
+    };
+
+    for (_, entity) in self.entities.iter_mut() {
+      entity.update(&mut entity_update);
+      entity.collide_obstacles(&self.obstacles);
+    }
+
+    self.entity_grid.clear();
+    for (id, entity) in self.entities.iter() {
+      let entity = entity.entity();
+      self
+        .entity_grid
+        .insert(*id, entity.pos.x, entity.pos.y, entity.radius.max(entity.aura));
+    }
+
+    for id in &self.players_id {
+      if let Some(hero) = heroes.get_mut(id) {
+        let target = hero.player();
+        let (x, y, radius) = (target.pos.x, target.pos.y, target.radius);
+        for entity_id in self.entity_grid.query(x, y, radius) {
+          if let Some(entity) = self.entities.get_mut(&entity_id) {
+            entity.interact(hero);
+          }
+        }
       }
     }
 
-    arr
+    self.refresh_players(|id| heroes.get(id).map(|hero| hero.player()));
+    for first_player in self.players.iter() {
+      let nearby = players_in_radius(
+        &self.players,
+        &self.player_grid,
+        first_player.pos.x,
+        first_player.pos.y,
+        first_player.radius,
+      );
+      for second_player in nearby {
+        if first_player.id != second_player.id
+          && second_player.downed
+          && distance(
+            second_player.pos.x - first_player.pos.x,
+            second_player.pos.y - first_player.pos.y,
+          ) <= first_player.radius + second_player.radius
+        {
+          if let Some(hero) = heroes.get_mut(&second_player.id) {
+            hero.res();
+          }
+        }
+      }
+    }
+
+    let mut spawned_entities = BTreeMap::new();
+    let entities_to_spawn = std::mem::take(&mut self.events.entities_to_spawn);
+    for entity in entities_to_spawn.iter() {
+      let id = self.add_entity(entity.clone());
+      spawned_entities.insert(id as u32, entity.pack());
+    }
+
+    let mut entities_diff = BTreeMap::new();
+    for (id, entity) in self.get_packed_entities().iter() {
+      if let Some(old_entity) = old_entities.get(&id) {
+        let (diff, changed) = old_entity.diff(&entity);
+        if changed {
+          entities_diff.insert(*id as u32, diff);
+        }
+      }
+    }
+
+    if !spawned_entities.is_empty() {
+      packages.push(Kind::NewEntities(Entities {
+        entities: spawned_entities,
+      }));
+    }
+
+    if !entities_to_remove.is_empty() {
+      packages.push(Kind::CloseEntities(CloseEntities {
+        ids: entities_to_remove,
+      }));
+    }
+
+    if !entities_diff.is_empty() {
+      packages.push(Kind::UpdateEntities(UpdateEntitiesMap {
+        items: entities_diff,
+      }));
+    }
+
+    self.swap_random();
+    packages
   }
 
   pub fn as_boundary(&self) -> Boundary {
@@ -134,4 +314,32 @@ impl Area {
       h: self.raw_area.h,
     }
   }
+
+  pub fn regions_at(&self, player: &Player) -> u32 {
+    let mut flags = 0;
+    for region in self.raw_area.regions.iter() {
+      if player.pos.x - player.radius >= region.x
+        && player.pos.x + player.radius <= region.x + region.w
+        && player.pos.y - player.radius >= region.y
+        && player.pos.y + player.radius <= region.y + region.h
+      {
+        flags |= region.kind.flag();
+      }
+    }
+    flags
+  }
+
+  pub fn spawn_point(&self, radius: f64) -> Option<Vector> {
+    let region = self
+      .raw_area
+      .regions
+      .iter()
+      .find(|region| region.kind == RegionKind::Spawn)?;
+    Some(Vector::rand(
+      region.x + radius,
+      region.y + radius,
+      region.x + region.w - radius,
+      region.y + region.h - radius,
+    ))
+  }
 }
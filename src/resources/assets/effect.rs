@@ -2,26 +2,31 @@ use crate::resources::assets::effects::slow::PlayerSlow;
 use crate::resources::assets::effects::PlayerEffectLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::effect::PlayerEffect;
+use crate::resources::stats::Modifier;
 use crate::resources::EffectUpdateProps;
 use napi::{Error, Status};
 use crate::resources::assets::effects::draining::PlayerDraining;
 use crate::resources::assets::effects::slipped::PlayerSlipped;
+use crate::resources::assets::effects::frozen::PlayerFrozen;
+use serde::{Deserialize, Serialize};
 
 macro_rules! effect_dispatch {
   ($self:expr, $method:ident($($arg:expr),*)) => {
     match $self {
       PlayerEffectWrapper::Slow(v) => v.$method($($arg),*),
       PlayerEffectWrapper::Draining(v) => v.$method($($arg),*),
-      PlayerEffectWrapper::Slipped(v) => v.$method($($arg),*)
+      PlayerEffectWrapper::Slipped(v) => v.$method($($arg),*),
+      PlayerEffectWrapper::Frozen(v) => v.$method($($arg),*)
     }
   };
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PlayerEffectWrapper {
   Slow(PlayerSlow),
   Draining(PlayerDraining),
   Slipped(PlayerSlipped),
+  Frozen(PlayerFrozen),
 }
 
 impl PlayerEffectWrapper {
@@ -30,6 +35,7 @@ impl PlayerEffectWrapper {
       0 => Ok(PlayerEffectWrapper::Slow(PlayerSlow::new(hero, caster_id))),
       1 => Ok(PlayerEffectWrapper::Draining(PlayerDraining::new(hero, caster_id))),
       2 => Ok(PlayerEffectWrapper::Slipped(PlayerSlipped::new(hero, caster_id))),
+      3 => Ok(PlayerEffectWrapper::Frozen(PlayerFrozen::new(hero, caster_id))),
       _ => Err(Error::new(
         Status::InvalidArg,
         "Unknown effect type: ".to_string() + name.to_string().as_str(),
@@ -41,12 +47,12 @@ impl PlayerEffectWrapper {
     effect_dispatch!(self, update(props));
   }
 
-  pub fn disable(&mut self, player: &mut HeroWrapper) {
-    effect_dispatch!(self, disable(player));
+  pub fn modifiers(&self) -> Vec<Modifier> {
+    effect_dispatch!(self, modifiers())
   }
 
-  pub fn enable(&mut self, player: &mut HeroWrapper) {
-    effect_dispatch!(self, enable(player));
+  pub fn disables_abilities(&self) -> bool {
+    effect_dispatch!(self, disables_abilities())
   }
 
   pub fn effect_id(&self) -> u64 {
@@ -1,9 +1,11 @@
+use crate::config::RegionKind;
 use crate::resources::assets::effects::PlayerEffectLogic;
 use crate::resources::assets::hero::HeroWrapper;
-use crate::resources::effect::PlayerEffect;
+use crate::resources::effect::{PlayerEffect, Stacking};
 use crate::resources::{distance, EffectUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerDraining {
   pub effect: PlayerEffect,
 }
@@ -11,29 +13,28 @@ pub struct PlayerDraining {
 impl PlayerDraining {
   pub fn new(target: &HeroWrapper, caster_id: u64) -> Self {
     let player = target.player();
-    let mut s = Self {
-      effect: PlayerEffect::new(1, player.id, caster_id),
-    };
-    s.effect.id = 1;
-    s
+    Self {
+      effect: PlayerEffect::new(1, player.id, caster_id)
+        .bound_to_caster(player)
+        .with_stacking(Stacking::PerCaster),
+    }
   }
 }
 
 impl PlayerEffectLogic for PlayerDraining {
-  fn enable(&mut self, player: &mut HeroWrapper) {
-  }
-  fn disable(&self, player: &mut HeroWrapper) {
-  }
   fn update(&mut self, props: &mut EffectUpdateProps<'_>) {
     let target = props.target.player_mut();
-    let caster = props.caster.entity();
+    let caster = match props.caster {
+      Some(caster) => caster.entity(),
+      None => return,
+    };
 
-    target.energy -= 16.0 * props.delta  as f64 / 1000.0;
+    target.energy -= 16.0 * props.delta / 1000.0;
 
     if distance(target.pos.x - caster.pos.x, target.pos.y - caster.pos.y) >= 150.0 + target.radius {
       self.effect.to_remove = true;
     }
-    if target.pos.x - target.radius < 0.0 && target.pos.x > props.boundary.w + target.radius {
+    if target.in_region(RegionKind::Safe) {
       self.effect.to_remove = true;
     }
   }
@@ -0,0 +1,49 @@
+use crate::config::Frozen;
+use crate::resources::assets::effects::PlayerEffectLogic;
+use crate::resources::assets::hero::HeroWrapper;
+use crate::resources::effect::PlayerEffect;
+use crate::resources::stats::{Modifier, Operation, Source, Stat};
+use crate::resources::EffectUpdateProps;
+use crate::CONFIG;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerFrozen {
+  pub effect: PlayerEffect,
+  config: Frozen,
+}
+
+impl PlayerFrozen {
+  pub fn new(target: &HeroWrapper, caster_id: u64) -> Self {
+    let player = target.player();
+    let config = CONFIG.lock().unwrap().effects.frozen.clone();
+    Self {
+      effect: PlayerEffect::new(3, player.id, caster_id).with_duration(config.duration),
+      config,
+    }
+  }
+}
+
+impl PlayerEffectLogic for PlayerFrozen {
+  fn update(&mut self, _: &mut EffectUpdateProps<'_>) {}
+  fn modifiers(&self) -> Vec<Modifier> {
+    vec![Modifier::new(
+      Stat::Speed,
+      Operation::Multiply,
+      self.config.speed_multiplier,
+      Source::Effect(self.effect.id),
+    )]
+  }
+  fn disables_abilities(&self) -> bool {
+    !self.config.abilities
+  }
+  fn effect(&self) -> &PlayerEffect {
+    &self.effect
+  }
+  fn effect_mut(&mut self) -> &mut PlayerEffect {
+    &mut self.effect
+  }
+  fn effect_id(&self) -> u64 {
+    self.effect.id
+  }
+}
@@ -1,15 +1,24 @@
-use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::effect::PlayerEffect;
+use crate::resources::stats::Modifier;
 use crate::resources::EffectUpdateProps;
 
 pub mod slow;
 pub mod draining;
 pub mod slipped;
+pub mod frozen;
+
+// How long an aura effect outlives the last tick its caster re-applied it
+pub const AURA_LINGER: f64 = 100.0;
 
 pub trait PlayerEffectLogic {
-  fn enable(&mut self, player: &mut HeroWrapper);
-  fn disable(&self, player: &mut HeroWrapper);
   fn update(&mut self, props: &mut EffectUpdateProps);
+  // Applied on top of the target's base stats while the effect is active
+  fn modifiers(&self) -> Vec<Modifier> {
+    Vec::new()
+  }
+  fn disables_abilities(&self) -> bool {
+    false
+  }
   fn effect(&self) -> &PlayerEffect;
   fn effect_mut(&mut self) -> &mut PlayerEffect;
   fn effect_id(&self) -> u64;
@@ -1,41 +1,38 @@
 use crate::resources::assets::effects::PlayerEffectLogic;
 use crate::resources::assets::hero::HeroWrapper;
-use crate::resources::effect::PlayerEffect;
-use crate::resources::{distance, EffectUpdateProps};
+use crate::resources::effect::{PlayerEffect, Stacking};
+use crate::resources::stats::{Modifier, Operation, Source, Stat};
+use crate::resources::EffectUpdateProps;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerSlipped {
   pub effect: PlayerEffect,
-  time: f64,
-  original_speed: f64
+  // The target's base speed, added on top of its speed while slipped
+  bonus: f64,
 }
 
 impl PlayerSlipped {
   pub fn new(target: &HeroWrapper, caster_id: u64) -> Self {
     let player = target.player();
-    let mut s = Self {
-      effect: PlayerEffect::new(2, player.id, caster_id),
-      time: 100.0,
-      original_speed: player.speed
-    };
-    s
+    Self {
+      effect: PlayerEffect::new(2, player.id, caster_id)
+        .with_duration(100.0)
+        .with_stacking(Stacking::Refresh),
+      bonus: player.base.speed,
+    }
   }
 }
 
 impl PlayerEffectLogic for PlayerSlipped {
-  fn enable(&mut self, hero: &mut HeroWrapper) {
-    hero.player_mut().speed = self.original_speed * 2.0;
-  }
-  fn disable(&self, hero: &mut HeroWrapper) {
-    hero.player_mut().speed = self.original_speed;
-  }
-  fn update(&mut self, props: &mut EffectUpdateProps<'_>) {
-    let target = props.target.player_mut();
-    let caster = props.caster.entity();
-    self.time -= props.delta as f64;
-    if self.time < 0.0 {
-      self.effect.to_remove = true;
-    }
+  fn update(&mut self, _: &mut EffectUpdateProps<'_>) {}
+  fn modifiers(&self) -> Vec<Modifier> {
+    vec![Modifier::new(
+      Stat::Speed,
+      Operation::Add,
+      self.bonus,
+      Source::Effect(self.effect.id),
+    )]
   }
   fn effect(&self) -> &PlayerEffect {
     &self.effect
@@ -1,44 +1,42 @@
-use crate::resources::assets::effects::PlayerEffectLogic;
+use crate::config::RegionKind;
+use crate::resources::assets::effects::{PlayerEffectLogic, AURA_LINGER};
 use crate::resources::assets::hero::HeroWrapper;
-use crate::resources::effect::PlayerEffect;
-use crate::resources::{distance, EffectUpdateProps};
+use crate::resources::effect::{PlayerEffect, Stacking};
+use crate::resources::stats::{Modifier, Operation, Source, Stat};
+use crate::resources::EffectUpdateProps;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerSlow {
-  pub target_speed: f64,
   pub effect: PlayerEffect,
 }
 
 impl PlayerSlow {
   pub fn new(target: &HeroWrapper, caster_id: u64) -> Self {
     let player = target.player();
-    let mut s = Self {
-      target_speed: player.speed,
-      effect: PlayerEffect::new(0, player.id, caster_id),
-    };
-    s.effect.id = 0;
-    s
+    Self {
+      effect: PlayerEffect::new(0, player.id, caster_id)
+        .with_duration(AURA_LINGER)
+        .with_stacking(Stacking::StrongestWins)
+        .with_strength(0.75),
+    }
   }
 }
 
 impl PlayerEffectLogic for PlayerSlow {
-  fn enable(&mut self, player: &mut HeroWrapper) {
-    player.player_mut().speed = self.target_speed * 0.25;
-  }
-  fn disable(&self, player: &mut HeroWrapper) {
-    player.player_mut().speed = self.target_speed;
-  }
   fn update(&mut self, props: &mut EffectUpdateProps<'_>) {
-    let target = props.target.player();
-    let caster = props.caster.entity();
-
-    if distance(target.pos.x - caster.pos.x, target.pos.y - caster.pos.y) >= 150.0 + target.radius {
-      self.effect.to_remove = true;
-    }
-    if target.pos.x - target.radius < 0.0 && target.pos.x > props.boundary.w + target.radius {
+    if props.target.player().in_region(RegionKind::Safe) {
       self.effect.to_remove = true;
     }
   }
+  fn modifiers(&self) -> Vec<Modifier> {
+    vec![Modifier::new(
+      Stat::Speed,
+      Operation::Multiply,
+      1.0 - self.effect.strength,
+      Source::Effect(self.effect.id),
+    )]
+  }
   fn effect(&self) -> &PlayerEffect {
     &self.effect
   }
@@ -1,15 +1,17 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
-use crate::resources::player::Player;
+use crate::resources::player::PlayerView;
 use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
 use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
 
 const MAX_DIST: f64 = 5.625 * 32.0;
 const ANGLE_INCREMENT: f64 = 0.04;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bee {
   entity: Entity,
 }
@@ -24,13 +26,10 @@ impl Bee {
 
 impl EntityLogic for Bee {
   fn update(&mut self, props: &mut EntityUpdateProps) {
-    let mut target: Option<&&Player> = None;
+    let mut target: Option<&PlayerView> = None;
     let mut last_distance = MAX_DIST;
-    for player in props.players.iter() {
-      if player.pos.x > -player.radius
-        && player.pos.x - player.radius < self.entity.boundary.w
-        && !player.downed
-      {
+    for player in props.players_in_radius(self.entity.pos.x, self.entity.pos.y, MAX_DIST) {
+      if !player.in_region(RegionKind::Safe) && !player.downed {
         let dist = distance(
           player.pos.x - self.entity.pos.x,
           player.pos.y - self.entity.pos.y,
@@ -58,7 +57,7 @@ impl EntityLogic for Bee {
         angle_diff += 2.0 * PI;
       }
 
-      let max_turn = ANGLE_INCREMENT * (props.delta as f64 / 16.67);
+      let max_turn = ANGLE_INCREMENT * (props.delta / 16.67);
       if angle_diff.abs() < max_turn {
         self.entity.angle = target_angle;
       } else {
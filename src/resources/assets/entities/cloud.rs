@@ -1,10 +1,12 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Cloud {
   entity: Entity,
   time_fix: f64
@@ -30,7 +32,7 @@ impl EntityLogic for Cloud {
     // self.entity.interact(player);
     let player = player.player_mut();
     if !player.immortal {
-      if player.pos.x > player.radius && player.pos.x - player.radius < self.entity.boundary.w {
+      if !player.in_region(RegionKind::Safe) {
         if distance(self.entity.pos.x - player.pos.x, self.entity.pos.y - player.pos.y) <= self.entity.radius + player.radius {
           let dx = player.pos.x -  self.entity.pos.x;
           let dy = player.pos.y - self.entity.pos.y;
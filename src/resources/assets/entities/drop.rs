@@ -4,8 +4,9 @@ use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::utils::vector::Vector;
 use crate::resources::{random, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Drop {
   entity: Entity,
   time_at_some_surface: f64,
@@ -71,7 +72,7 @@ impl EntityLogic for Drop {
     self.collide();
 
     if self.time_at_some_surface > 0.0 {
-      self.time_at_some_surface -= props.delta as f64;
+      self.time_at_some_surface -= props.delta;
       self.entity.vel.y = 0.0;
       self.entity.alpha = 1.0 - self.time_at_some_surface / self.start_time;
       self.entity.harmless = true;
@@ -84,12 +85,12 @@ impl EntityLogic for Drop {
     } else if self.time_at_some_surface < 0.0 {
       self.entity.vel.y = 0.0;
       self.entity.alpha = -self.time_at_some_surface / self.start_time;
-      self.time_at_some_surface += props.delta as f64;
+      self.time_at_some_surface += props.delta;
       if self.time_at_some_surface >= 0.0 {
         self.re_spawn();
       }
     } else {
-      self.speed_time -= props.delta as f64;
+      self.speed_time -= props.delta;
       if self.spawned {
         self.entity.vel.y = self.entity.speed;
       } else {
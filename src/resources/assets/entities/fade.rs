@@ -3,8 +3,9 @@ use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Fade {
   entity: Entity,
   timer: f64,
@@ -30,7 +31,7 @@ impl EntityLogic for Fade {
     self.entity.update(props);
     self.entity.collide();
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
 
     let period = MAX_TIME;
 
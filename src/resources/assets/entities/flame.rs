@@ -4,8 +4,9 @@ use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Flame {
   entity: Entity,
   timer: f64,
@@ -24,7 +25,7 @@ impl EntityLogic for Flame {
     self.entity.update(props);
     self.entity.collide();
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
     if self.timer >= 32.0 * ((self.entity.radius * 2.0) / self.entity.speed) {
       let mut trail = FlameTrail::new(
         EntityProps {
@@ -65,7 +66,7 @@ impl EntityLogic for Flame {
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FlameTrail {
   pub entity: Entity,
   timer: f64,
@@ -87,7 +88,7 @@ impl EntityLogic for FlameTrail {
     self.entity.update(props);
     self.entity.collide();
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
     self.entity.alpha = 1.0 - self.timer / (5000.0 / self.owner_speed);
     if self.timer >= 5000.0 / self.owner_speed {
       self.entity.to_remove = true;
@@ -1,13 +1,16 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::flame::FlameTrail;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
-use crate::resources::player::Player;
+use crate::resources::obstacle::Obstacle;
+use crate::resources::player::PlayerView;
 use crate::resources::{distance, random, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FlameSniper {
   entity: Entity,
   timer: f64,
@@ -28,16 +31,13 @@ impl EntityLogic for FlameSniper {
     self.entity.update(props);
     self.entity.collide();
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
 
     if self.timer > 6000.0 {
-      let mut target: Option<&&Player> = None;
+      let mut target: Option<&PlayerView> = None;
       let mut last_distance = 20.0 * 32.0;
-      for player in props.players.iter() {
-        if player.pos.x > -player.radius
-          && player.pos.x - player.radius < self.entity.boundary.w
-          && !player.downed
-        {
+      for player in props.players_in_radius(self.entity.pos.x, self.entity.pos.y, 20.0 * 32.0) {
+        if !player.in_region(RegionKind::Safe) && !player.downed {
           let dist = distance(
             player.pos.x - self.entity.pos.x,
             player.pos.y - self.entity.pos.y,
@@ -96,7 +96,7 @@ impl EntityLogic for FlameSniper {
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FlameBullet {
   pub entity: Entity,
   timer: f64,
@@ -139,7 +139,7 @@ impl EntityLogic for FlameBullet {
     self.entity.update(props);
     FlameBullet::collide(&mut self.entity);
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
     if self.timer >= 32.0 * ((self.entity.radius * 2.0) / self.entity.speed) {
       let mut trail = FlameTrail::new(
         EntityProps {
@@ -177,4 +177,10 @@ impl EntityLogic for FlameBullet {
   fn entity_mut(&mut self) -> &mut Entity {
     &mut self.entity
   }
+
+  fn collide_obstacles(&mut self, obstacles: &[Obstacle]) {
+    if self.entity.collide_obstacles(obstacles) {
+      self.entity.to_remove = true;
+    }
+  }
 }
@@ -0,0 +1,88 @@
+use crate::bus::PlayerEvent;
+use crate::config::RegionKind;
+use crate::proto::PackedEntity;
+use crate::resources::assets::entities::EntityLogic;
+use crate::resources::assets::hero::HeroWrapper;
+use crate::resources::entity::Entity;
+use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Freezing {
+  entity: Entity,
+  players_in_aura: Vec<i64>,
+  frozen_players: Vec<i64>,
+}
+
+impl Freezing {
+  pub fn new(props: EntityProps, _: AdditionalEntityProps) -> Self {
+    let mut entity = Entity::new(props);
+    entity.type_id = 28;
+    entity.state = 1;
+    entity.state_metadata = 100.0;
+    entity.aura = 100.0;
+    Self {
+      entity,
+      players_in_aura: Vec::new(),
+      frozen_players: Vec::new(),
+    }
+  }
+}
+
+impl EntityLogic for Freezing {
+  fn update(&mut self, props: &mut EntityUpdateProps) {
+    self.entity.update(props);
+    self.entity.collide();
+
+    // Only players entering the aura are frozen, so a thawed player can still walk out of it
+    for player_id in self.players_in_aura.iter() {
+      if !self.frozen_players.contains(player_id) {
+        props.event_bus.players_events.push(PlayerEvent::AddEffect {
+          player_id: *player_id,
+          effect_id: 3,
+          caster_id: self.entity.id,
+          caster_type: self.entity.type_id,
+        })
+      }
+    }
+    self.frozen_players = std::mem::take(&mut self.players_in_aura);
+  }
+
+  fn interact(&mut self, hero: &mut HeroWrapper) {
+    let player = hero.player_mut();
+
+    if distance(
+      player.pos.x - self.entity.pos.x,
+      player.pos.y - self.entity.pos.y,
+    ) <= self.entity.aura + player.radius
+      && !player.in_region(RegionKind::Safe)
+      && !player.downed
+    {
+      self.players_in_aura.push(player.id);
+    }
+
+    if !self.entity.harmless && !player.in_region(RegionKind::Safe) {
+      if !player.immortal && !player.downed {
+        if distance(
+          player.pos.x - self.entity.pos.x,
+          player.pos.y - self.entity.pos.y,
+        ) <= self.entity.radius + player.radius
+        {
+          player.knock();
+        }
+      }
+    }
+  }
+
+  fn pack(&self) -> PackedEntity {
+    self.entity.pack()
+  }
+
+  fn entity(&self) -> &Entity {
+    &self.entity
+  }
+
+  fn entity_mut(&mut self) -> &mut Entity {
+    &mut self.entity
+  }
+}
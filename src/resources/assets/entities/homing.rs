@@ -1,14 +1,16 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
-use crate::resources::player::Player;
+use crate::resources::player::PlayerView;
 use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
 const MAX_DIST: f64 = 5.625 * 32.0;
 const ANGLE_INCREMENT: f64 = 0.04;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Homing {
   entity: Entity,
 }
@@ -23,13 +25,10 @@ impl Homing {
 
 impl EntityLogic for Homing {
   fn update(&mut self, props: &mut EntityUpdateProps) {
-    let mut target: Option<&&Player> = None;
+    let mut target: Option<&PlayerView> = None;
     let mut last_distance = MAX_DIST;
-    for player in props.players.iter() {
-      if player.pos.x > -player.radius
-        && player.pos.x - player.radius < self.entity.boundary.w
-        && !player.downed
-      {
+    for player in props.players_in_radius(self.entity.pos.x, self.entity.pos.y, MAX_DIST) {
+      if !player.in_region(RegionKind::Safe) && !player.downed {
         let dist = distance(
           player.pos.x - self.entity.pos.x,
           player.pos.y - self.entity.pos.y,
@@ -50,9 +49,9 @@ impl EntityLogic for Homing {
       self.entity.vel_to_angle();
       if angle_diff.abs() >= ANGLE_INCREMENT {
         if angle_diff < 0.0 {
-          self.entity.angle -= ANGLE_INCREMENT * (props.delta as f64 / 30.0);
+          self.entity.angle -= ANGLE_INCREMENT * (props.delta / 30.0);
         } else {
-          self.entity.angle += ANGLE_INCREMENT * (props.delta as f64 / 30.0);
+          self.entity.angle += ANGLE_INCREMENT * (props.delta / 30.0);
         }
         self.entity.angle_to_vel();
       }
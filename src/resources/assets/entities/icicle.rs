@@ -3,11 +3,12 @@ use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{random, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Icicle {
   entity: Entity,
-  timer: i64,
+  timer: f64,
   wall_hit: bool,
 }
 
@@ -18,7 +19,7 @@ impl Icicle {
     entity.vel.x = 0.0;
     entity.vel.y = ((random(0.0, 1.0) * 2.0).floor() * 2.0 - 1.0) * entity.speed;
     entity.collide();
-    Self { entity, wall_hit: false, timer: 0 }
+    Self { entity, wall_hit: false, timer: 0.0 }
   }
 
   fn collide(&mut self) {
@@ -51,8 +52,8 @@ impl EntityLogic for Icicle {
     if self.wall_hit {
       self.timer += props.delta;
       self.entity.friction = 1.0;
-      if self.timer > 2500 {
-        self.timer = 0;
+      if self.timer > 2500.0 {
+        self.timer = 0.0;
         self.wall_hit = false;
         self.entity.friction = 0.0;
         self.entity.angle_to_vel();
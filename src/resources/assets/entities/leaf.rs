@@ -1,11 +1,13 @@
 use crate::bus::PlayerEvent;
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{distance, random, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Leaf {
   entity: Entity,
   time_spawn: f64,
@@ -41,7 +43,7 @@ impl EntityLogic for Leaf {
     self.entity.collide();
 
     if self.time_spawn > 0.0 {
-      self.time_spawn -= props.delta as f64;
+      self.time_spawn -= props.delta;
       self.entity.radius = self.start_radius * 2.0 * 0.5_f64.max(self.time_spawn / 1000.0);
       self.entity.alpha = 1.0 - self.time_spawn / 1000.0;
     } else if self.time_spawn <= 0.0 && self.entity.harmless {
@@ -50,7 +52,7 @@ impl EntityLogic for Leaf {
       self.time_spawn = 0.0;
     }
     if self.remove {
-      self.remove_time -= props.delta as f64;
+      self.remove_time -= props.delta;
       self.entity.harmless = true;
       self.entity.alpha = self.remove_time / 500.0;
       self.entity.radius =
@@ -64,6 +66,7 @@ impl EntityLogic for Leaf {
         player_id: *id,
         effect_id: 2,
         caster_id: self.entity.id,
+        caster_type: self.entity.type_id,
       })
     }
     self.players.clear();
@@ -71,10 +74,7 @@ impl EntityLogic for Leaf {
 
   fn interact(&mut self, hero: &mut HeroWrapper) {
     let player = hero.player_mut();
-    if !self.entity.harmless
-        && player.pos.x > -player.radius
-        && player.pos.x - player.radius < self.entity.boundary.w
-    {
+    if !self.entity.harmless && !player.in_region(RegionKind::Safe) {
       if !player.immortal && !player.downed {
         if distance(player.pos.x - self.entity.pos.x, player.pos.y - self.entity.pos.y)
             <= self.entity.radius + player.radius
@@ -1,6 +1,7 @@
 use crate::proto::PackedEntity;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
+use crate::resources::obstacle::Obstacle;
 use crate::resources::EntityUpdateProps;
 
 pub mod bee;
@@ -21,6 +22,7 @@ pub mod draining;
 pub mod leaf;
 pub mod cloud;
 pub mod stormcloud;
+pub mod freezing;
 
 pub trait EntityLogic {
   fn update(&mut self, props: &mut EntityUpdateProps);
@@ -28,4 +30,7 @@ pub trait EntityLogic {
   fn pack(&self) -> PackedEntity;
   fn entity(&self) -> &Entity;
   fn entity_mut(&mut self) -> &mut Entity;
+  fn collide_obstacles(&mut self, obstacles: &[Obstacle]) {
+    self.entity_mut().collide_obstacles(obstacles);
+  }
 }
@@ -3,8 +3,9 @@ use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Sizer {
   entity: Entity,
   min_radius: f64,
@@ -36,7 +37,7 @@ impl EntityLogic for Sizer {
         self.growing = false;
       }
     } else {
-      self.entity.radius -= ((props.delta as f64 / 30.0) * 0.08) * self.min_radius;
+      self.entity.radius -= ((props.delta / 30.0) * 0.08) * self.min_radius;
       if self.entity.radius < self.min_radius {
         self.growing = true;
       }
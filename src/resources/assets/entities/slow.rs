@@ -1,11 +1,13 @@
 use crate::bus::PlayerEvent;
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Slow {
   entity: Entity,
   players_in_aura: Vec<i64>,
@@ -35,6 +37,7 @@ impl EntityLogic for Slow {
         player_id: *player_id,
         effect_id: 0,
         caster_id: self.entity.id,
+        caster_type: self.entity.type_id,
       })
     }
     self.players_in_aura.clear();
@@ -51,10 +54,7 @@ impl EntityLogic for Slow {
       self.players_in_aura.push(player.id);
     }
 
-    if !self.entity.harmless
-      && player.pos.x > -player.radius
-      && player.pos.x - player.radius < self.entity.boundary.w
-    {
+    if !self.entity.harmless && !player.in_region(RegionKind::Safe) {
       if !player.immortal && !player.downed {
         if distance(
           player.pos.x - self.entity.pos.x,
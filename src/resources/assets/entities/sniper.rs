@@ -1,12 +1,15 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
-use crate::resources::player::Player;
+use crate::resources::obstacle::Obstacle;
+use crate::resources::player::PlayerView;
 use crate::resources::{distance, random, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Sniper {
   entity: Entity,
   timer: f64,
@@ -28,16 +31,13 @@ impl EntityLogic for Sniper {
     self.entity.update(props);
     self.entity.collide();
 
-    self.timer += props.delta as f64;
+    self.timer += props.delta;
 
     if self.timer > 3000.0 {
-      let mut target: Option<&&Player> = None;
+      let mut target: Option<&PlayerView> = None;
       let mut last_distance = 20.0 * 32.0;
-      for player in props.players.iter() {
-        if player.pos.x > -player.radius
-          && player.pos.x - player.radius < self.entity.boundary.w
-          && !player.downed
-        {
+      for player in props.players_in_radius(self.entity.pos.x, self.entity.pos.y, 20.0 * 32.0) {
+        if !player.in_region(RegionKind::Safe) && !player.downed {
           let dist = distance(
             player.pos.x - self.entity.pos.x,
             player.pos.y - self.entity.pos.y,
@@ -95,7 +95,7 @@ impl EntityLogic for Sniper {
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bullet {
   pub entity: Entity,
 }
@@ -150,4 +150,10 @@ impl EntityLogic for Bullet {
   fn entity_mut(&mut self) -> &mut Entity {
     &mut self.entity
   }
+
+  fn collide_obstacles(&mut self, obstacles: &[Obstacle]) {
+    if self.entity.collide_obstacles(obstacles) {
+      self.entity.to_remove = true;
+    }
+  }
 }
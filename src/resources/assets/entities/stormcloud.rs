@@ -1,10 +1,12 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
 use crate::resources::{distance, AdditionalEntityProps, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StormCloud {
   entity: Entity,
   time_fix: f64,
@@ -29,14 +31,14 @@ impl EntityLogic for StormCloud {
     self.entity.update(props);
     self.entity.collide();
     self.time_fix = props.time_fix;
-    self.timer = (self.timer % 2000.0) + props.delta as f64;
+    self.timer = (self.timer % 2000.0) + props.delta;
     self.entity.alpha = ((self.timer / 1000.0).sin()).abs();
   }
 
   fn interact(&mut self, player: &mut HeroWrapper) {
     self.entity.interact(player);
     let player = player.player_mut();
-    if player.pos.x > player.radius && player.pos.x - player.radius < self.entity.boundary.w {
+    if !player.in_region(RegionKind::Safe) {
       if distance(
         self.entity.pos.x - player.pos.x,
         self.entity.pos.y - player.pos.y,
@@ -2,10 +2,12 @@ use crate::proto::PackedEntity;
 use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
+use crate::resources::obstacle::Obstacle;
 use crate::resources::utils::vector::Vector;
 use crate::resources::{AdditionalEntityProps, Boundary, EntityProps, EntityUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Wall {
   entity: Entity,
   dir_act: i64,
@@ -136,4 +138,6 @@ impl EntityLogic for Wall {
   fn entity_mut(&mut self) -> &mut Entity {
     &mut self.entity
   }
+
+  fn collide_obstacles(&mut self, _: &[Obstacle]) {}
 }
@@ -16,12 +16,15 @@ use crate::resources::assets::entities::EntityLogic;
 use crate::resources::assets::entities::cloud::Cloud;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
+use crate::resources::obstacle::Obstacle;
 use crate::resources::{AdditionalEntityProps, EntityProps, EntityUpdateProps};
 use napi::{Error, Status};
 use crate::resources::assets::entities::draining::Draining;
+use crate::resources::assets::entities::freezing::Freezing;
 use crate::resources::assets::entities::icicle::Icicle;
 use crate::resources::assets::entities::leaf::Leaf;
 use crate::resources::assets::entities::stormcloud::StormCloud;
+use serde::{Deserialize, Serialize};
 
 macro_rules! entity_dispatch {
   ($self:expr, $method:ident($($arg:expr),*)) => {
@@ -48,11 +51,12 @@ macro_rules! entity_dispatch {
       EntityWrapper::Leaf(v) => v.$method($($arg),*),
       EntityWrapper::Cloud(v) => v.$method($($arg),*),
       EntityWrapper::StormCloud(v) => v.$method($($arg),*),
+      EntityWrapper::Freezing(v) => v.$method($($arg),*),
     }
   };
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum EntityWrapper {
   Normal(Normal),
   Flame(Flame),
@@ -75,7 +79,8 @@ pub enum EntityWrapper {
   Draining(Draining),
   Leaf(Leaf),
   Cloud(Cloud),
-  StormCloud(StormCloud)
+  StormCloud(StormCloud),
+  Freezing(Freezing),
 }
 
 impl EntityWrapper {
@@ -107,6 +112,7 @@ impl EntityWrapper {
       "leaf" => Ok(EntityWrapper::Leaf(Leaf::new(*props, additional))),
       "cloud" => Ok(EntityWrapper::Cloud(Cloud::new(*props, additional))),
       "storm_cloud" => Ok(EntityWrapper::StormCloud(StormCloud::new(*props, additional))),
+      "freezing" => Ok(EntityWrapper::Freezing(Freezing::new(*props, additional))),
       _ => Err(Error::new(
         Status::InvalidArg,
         "Unknown enemy type: ".to_string() + name,
@@ -133,4 +139,8 @@ impl EntityWrapper {
   pub fn entity_mut(&mut self) -> &mut Entity {
     entity_dispatch!(self, entity_mut())
   }
+
+  pub fn collide_obstacles(&mut self, obstacles: &[Obstacle]) {
+    entity_dispatch!(self, collide_obstacles(obstacles));
+  }
 }
@@ -1,11 +1,15 @@
 use crate::proto::PackedPlayer;
 use crate::resources::assets::heroes::maven::Maven;
-use crate::resources::assets::heroes::Hero;
+use crate::resources::assets::heroes::{AbilityStatus, Hero, HeroInfo};
+use crate::resources::obstacle::Obstacle;
 use crate::resources::player::Player;
+use crate::resources::stats::Modifier;
 use crate::resources::utils::input::Input;
 use crate::resources::utils::join::JoinProps;
 use crate::resources::{Boundary, PlayerUpdateProps};
 use napi::{Error, Status};
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
 
 macro_rules! hero_dispatch {
   ($self:expr, $method:ident($($arg:expr),*)) => {
@@ -15,11 +19,16 @@ macro_rules! hero_dispatch {
   };
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum HeroWrapper {
   Maven(Maven),
 }
 
+#[napi]
+pub fn get_heroes() -> Vec<HeroInfo> {
+  HeroWrapper::heroes()
+}
+
 impl HeroWrapper {
   pub fn new(name: &str, props: JoinProps) -> Result<Self, Error> {
     match name {
@@ -30,6 +39,10 @@ impl HeroWrapper {
       )),
     }
   }
+
+  pub fn heroes() -> Vec<HeroInfo> {
+    vec![Maven::info()]
+  }
   pub fn update(&mut self, props: &mut PlayerUpdateProps) {
     hero_dispatch!(self, update(props));
   }
@@ -42,12 +55,26 @@ impl HeroWrapper {
   pub fn res(&mut self) {
     hero_dispatch!(self, res());
   }
-  pub fn collide(&mut self, boundary: Boundary) {
-    hero_dispatch!(self, collide(boundary));
+  pub fn collide(&mut self, boundary: Boundary, obstacles: &[Obstacle]) {
+    hero_dispatch!(self, collide(boundary, obstacles));
   }
 
   pub fn pack(&self) -> PackedPlayer {
-    hero_dispatch!(self, pack())
+    let mut packed = hero_dispatch!(self, pack());
+    packed.abilities = self.abilities().iter().map(|ability| ability.pack()).collect();
+    packed
+  }
+
+  pub fn abilities(&self) -> Vec<AbilityStatus> {
+    hero_dispatch!(self, abilities())
+  }
+
+  pub fn modifiers(&self) -> Vec<Modifier> {
+    hero_dispatch!(self, modifiers())
+  }
+
+  pub fn immortal(&self) -> bool {
+    hero_dispatch!(self, immortal())
   }
 
   pub fn player(&self) -> &Player {
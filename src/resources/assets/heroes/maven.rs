@@ -1,40 +1,123 @@
 use crate::proto::PackedPlayer;
-use crate::resources::assets::heroes::Hero;
+use crate::resources::assets::heroes::{AbilityInfo, AbilityStatus, Hero, HeroInfo};
+use crate::resources::obstacle::Obstacle;
 use crate::resources::player::Player;
 use crate::resources::utils::input::Input;
 use crate::resources::utils::join::JoinProps;
 use crate::resources::{distance, Boundary, PlayerUpdateProps};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Maven {
   player: Player,
   first_ability_active: bool,
   first_ability_cooldown: f64,
+  second_ability_timer: f64,
   second_ability_cooldown: f64,
 }
 
+const FIRST_ABILITY_COST: f64 = 30.0;
+const FIRST_ABILITY_COOLDOWN: f64 = 8000.0;
+const SECOND_ABILITY_COST: f64 = 20.0;
+const SECOND_ABILITY_COOLDOWN: f64 = 12000.0;
+const SECOND_ABILITY_DURATION: f64 = 3000.0;
+
+const AURA_STATE: u64 = 1;
+const SANCTUARY_STATE: u64 = 2;
+const AURA_RADIUS: f64 = 120.0;
+
 impl Maven {
+  pub const ID: u32 = 0;
+
   pub fn new(props: JoinProps) -> Self {
+    let mut player = Player::new(props);
+    player.hero = Maven::ID;
     Self {
-      player: Player::new(props),
+      player,
       first_ability_active: false,
       first_ability_cooldown: 0.0,
+      second_ability_timer: 0.0,
       second_ability_cooldown: 0.0,
     }
   }
 
+  pub fn info() -> HeroInfo {
+    HeroInfo {
+      name: "maven".to_string(),
+      id: Maven::ID,
+      abilities: vec![
+        AbilityInfo {
+          name: "Rescue Aura".to_string(),
+          description: "Revives downed players within 120 units while active, draining 24 energy per second".to_string(),
+          energy_cost: FIRST_ABILITY_COST,
+          cooldown: FIRST_ABILITY_COOLDOWN,
+        },
+        AbilityInfo {
+          name: "Sanctuary".to_string(),
+          description: "Makes Maven immune to enemies for 3 seconds".to_string(),
+          energy_cost: SECOND_ABILITY_COST,
+          cooldown: SECOND_ABILITY_COOLDOWN,
+        },
+      ],
+    }
+  }
+
+  // state_meta is the aura radius while the aura is up, otherwise Sanctuary's duration in seconds
+  fn update_state(&mut self) {
+    self.player.state = 0;
+    self.player.state_meta = 0.0;
+    if self.second_ability_timer > 0.0 {
+      self.player.state |= SANCTUARY_STATE;
+      self.player.state_meta = SECOND_ABILITY_DURATION / 1000.0;
+    }
+    if self.first_ability_active {
+      self.player.state |= AURA_STATE;
+      self.player.state_meta = AURA_RADIUS;
+    }
+  }
+
   fn activate_first_ability(&mut self) {
     if self.first_ability_active {
       self.first_ability_active = false;
-      self.player.state = 0;
+      self.update_state();
     }
-    if self.player.energy > 30.0 && !self.player.downed && self.first_ability_cooldown <= 0.0 {
+    if self.player.energy > FIRST_ABILITY_COST
+      && !self.player.downed
+      && self.player.can_use_abilities()
+      && self.first_ability_cooldown <= 0.0
+    {
       self.first_ability_active = !self.first_ability_active;
       if self.first_ability_active {
-        self.player.energy -= 30.0;
-        self.first_ability_cooldown = 8000.0;
-        self.player.state = 1;
-        self.player.state_meta = 120.0;
+        self.player.energy -= FIRST_ABILITY_COST;
+        self.first_ability_cooldown = FIRST_ABILITY_COOLDOWN;
+        self.update_state();
+      }
+    }
+  }
+
+  fn activate_second_ability(&mut self) {
+    if self.player.energy > SECOND_ABILITY_COST
+      && !self.player.downed
+      && self.player.can_use_abilities()
+      && self.second_ability_cooldown <= 0.0
+    {
+      self.player.energy -= SECOND_ABILITY_COST;
+      self.second_ability_cooldown = SECOND_ABILITY_COOLDOWN;
+      self.second_ability_timer = SECOND_ABILITY_DURATION;
+      self.update_state();
+    }
+  }
+
+  fn update_second_ability(&mut self, delta: f64) {
+    if self.second_ability_cooldown >= 0.0 {
+      self.second_ability_cooldown -= delta;
+    }
+
+    if self.second_ability_timer > 0.0 {
+      self.second_ability_timer -= delta;
+      if self.second_ability_timer <= 0.0 || self.player.downed {
+        self.second_ability_timer = 0.0;
+        self.update_state();
       }
     }
   }
@@ -45,28 +128,29 @@ impl Hero for Maven {
     self.player.update(props);
 
     if self.first_ability_cooldown >= 0.0 {
-      self.first_ability_cooldown -= props.delta as f64;
+      self.first_ability_cooldown -= props.delta;
     }
+    self.update_second_ability(props.delta);
 
     if self.first_ability_active {
-      self.player.energy -= (props.delta as f64 / 1000.0) * 24.0;
+      self.player.energy -= (props.delta / 1000.0) * 24.0;
       if self.player.energy <= 0.0 {
         self.first_ability_active = false;
         self.player.energy = 0.0;
-        self.player.state = 0;
+        self.update_state();
         return;
       }
-      if self.player.downed {
+      if self.player.downed || !self.player.can_use_abilities() {
         self.first_ability_active = false;
-        self.player.state = 0;
+        self.update_state();
         return;
       }
 
-      for player in props.players.iter() {
+      for player in props.players_in_radius(self.player.pos.x, self.player.pos.y, AURA_RADIUS) {
         if distance(
           player.pos.x - self.player.pos.x,
           player.pos.y - self.player.pos.y,
-        ) <= 120.0 + player.radius
+        ) <= AURA_RADIUS + player.radius
           && player.downed
         {
           props
@@ -83,7 +167,10 @@ impl Hero for Maven {
       self.activate_first_ability();
       input.first_ability = false;
     }
-    input.second_ability = false;
+    if input.second_ability {
+      self.activate_second_ability();
+      input.second_ability = false;
+    }
   }
 
   fn knock(&mut self) {
@@ -94,14 +181,35 @@ impl Hero for Maven {
     self.player.res();
   }
 
-  fn collide(&mut self, boundary: Boundary) {
-    self.player.collide(boundary);
+  fn collide(&mut self, boundary: Boundary, obstacles: &[Obstacle]) {
+    self.player.collide(boundary, obstacles);
   }
 
   fn pack(&self) -> PackedPlayer {
     self.player.pack()
   }
 
+  fn abilities(&self) -> Vec<AbilityStatus> {
+    vec![
+      AbilityStatus {
+        cooldown: self.first_ability_cooldown,
+        cooldown_total: FIRST_ABILITY_COOLDOWN,
+        energy_cost: FIRST_ABILITY_COST,
+        active: self.first_ability_active,
+      },
+      AbilityStatus {
+        cooldown: self.second_ability_cooldown,
+        cooldown_total: SECOND_ABILITY_COOLDOWN,
+        energy_cost: SECOND_ABILITY_COST,
+        active: self.second_ability_timer > 0.0,
+      },
+    ]
+  }
+
+  fn immortal(&self) -> bool {
+    self.second_ability_timer > 0.0
+  }
+
   fn player(&self) -> &Player {
     &self.player
   }
@@ -1,17 +1,67 @@
-use crate::proto::PackedPlayer;
+use crate::proto::{PackedAbility, PackedPlayer};
+use crate::resources::obstacle::Obstacle;
 use crate::resources::player::Player;
+use crate::resources::stats::Modifier;
 use crate::resources::utils::input::Input;
 use crate::resources::{Boundary, PlayerUpdateProps};
+use napi_derive::napi;
 
 pub mod maven;
 
+#[napi(object)]
+pub struct AbilityInfo {
+  pub name: String,
+  pub description: String,
+  pub energy_cost: f64,
+  pub cooldown: f64,
+}
+
+#[napi(object)]
+pub struct HeroInfo {
+  pub name: String,
+  pub id: u32,
+  pub abilities: Vec<AbilityInfo>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AbilityStatus {
+  pub cooldown: f64,
+  pub cooldown_total: f64,
+  pub energy_cost: f64,
+  pub active: bool,
+}
+
+impl AbilityStatus {
+  pub fn pack(&self) -> PackedAbility {
+    PackedAbility {
+      cooldown: pack_cooldown(self.cooldown),
+      cooldown_total: pack_cooldown(self.cooldown_total),
+      energy_cost: (self.energy_cost * 2.0).round().abs() as u32,
+      active: self.active,
+    }
+  }
+}
+
 pub trait Hero {
   fn update(&mut self, props: &mut PlayerUpdateProps);
   fn input(&mut self, input: &mut Input);
   fn knock(&mut self);
   fn res(&mut self);
-  fn collide(&mut self, boundary: Boundary);
+  fn collide(&mut self, boundary: Boundary, obstacles: &[Obstacle]);
   fn pack(&self) -> PackedPlayer;
+  fn abilities(&self) -> Vec<AbilityStatus>;
+  // Stat modifiers granted by the hero's own abilities
+  fn modifiers(&self) -> Vec<Modifier> {
+    Vec::new()
+  }
+  // Immortality granted by the hero's own abilities
+  fn immortal(&self) -> bool {
+    false
+  }
   fn player(&self) -> &Player;
   fn player_mut(&mut self) -> &mut Player;
 }
+
+pub fn pack_cooldown(cooldown: f64) -> u32 {
+  (cooldown.max(0.0) / 100.0).ceil() as u32
+}
@@ -1,12 +1,34 @@
-use crate::resources::assets::hero::HeroWrapper;
+use crate::proto::{PackedEffect, RemovedEffect};
 use crate::resources::player::Player;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+// How a re-application of an effect that is already on the target is resolved
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Stacking {
+  // One instance per target, re-applying restarts the duration
+  Refresh,
+  // One instance per target counting applications up to max
+  Stack { max: u32 },
+  // One instance per target, a stronger application replaces the current one
+  StrongestWins,
+  // One instance per caster
+  PerCaster,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerEffect {
   pub to_remove: bool,
   pub id: u64,
   pub target_id: i64,
   pub caster_id: u64,
+  pub caster_type: u64,
+  // The area the caster lives in, None for effects that run on their own
+  pub caster_area: Option<(String, u64)>,
+  pub duration: Option<f64>,
+  pub remaining: Option<f64>,
+  pub stacks: u32,
+  pub stacking: Stacking,
+  pub strength: f64,
 }
 
 impl PlayerEffect {
@@ -16,9 +38,145 @@ impl PlayerEffect {
       id,
       target_id,
       caster_id,
+      caster_type: 0,
+      caster_area: None,
+      duration: None,
+      remaining: None,
+      stacks: 1,
+      stacking: Stacking::Refresh,
+      strength: 1.0,
+    }
+  }
+
+  pub fn with_duration(mut self, duration: f64) -> Self {
+    self.duration = Some(duration);
+    self.remaining = Some(duration);
+    self
+  }
+
+  // Ends the effect once its caster is gone or the target leaves the area it was applied in
+  pub fn bound_to_caster(mut self, target: &Player) -> Self {
+    self.caster_area = Some((target.world.clone(), target.area));
+    self
+  }
+
+  pub fn with_stacking(mut self, stacking: Stacking) -> Self {
+    self.stacking = stacking;
+    self
+  }
+
+  pub fn with_strength(mut self, strength: f64) -> Self {
+    self.strength = strength;
+    self
+  }
+
+  // Effects sharing a key are the same instance on a target
+  pub fn key(&self) -> (u64, u64) {
+    match self.stacking {
+      Stacking::PerCaster => (self.id, self.caster_id),
+      _ => (self.id, 0),
+    }
+  }
+
+  pub fn tick(&mut self, delta: f64) {
+    if let Some(remaining) = &mut self.remaining {
+      *remaining -= delta;
+      if *remaining <= 0.0 {
+        self.to_remove = true;
+      }
+    }
+  }
+
+  pub fn pack(&self) -> PackedEffect {
+    PackedEffect {
+      player_id: self.target_id as u32,
+      effect_type: self.id as u32,
+      instance: self.key().1 as u32,
+      caster_id: self.caster_id as u32,
+      caster_type: self.caster_type as u32,
+      duration: self.duration.map(|duration| duration as f32),
+      remaining: self.remaining.map(|remaining| remaining.max(0.0) as f32),
+      stacks: self.stacks,
+    }
+  }
+
+  pub fn pack_removed(&self) -> RemovedEffect {
+    RemovedEffect {
+      player_id: self.target_id as u32,
+      effect_type: self.id as u32,
+      instance: self.key().1 as u32,
+    }
+  }
+
+  // Returns true when the new application should replace this instance
+  pub fn reapply(&mut self, other: &PlayerEffect) -> bool {
+    // An application from another area means the current caster is no longer reachable
+    if self.caster_area != other.caster_area {
+      return true;
+    }
+    if self.stacking == Stacking::StrongestWins && other.strength > self.strength {
+      return true;
+    }
+    if let Stacking::Stack { max } = self.stacking {
+      self.stacks = (self.stacks + 1).min(max);
     }
+    self.remaining = match (self.remaining, other.duration) {
+      (Some(remaining), Some(duration)) => Some(remaining.max(duration)),
+      (_, duration) => duration,
+    };
+    false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn effect(stacking: Stacking, caster_id: u64) -> PlayerEffect {
+    PlayerEffect::new(2, 1, caster_id)
+      .with_duration(100.0)
+      .with_stacking(stacking)
+  }
+
+  #[test]
+  fn refresh_restarts_the_duration_without_stacking() {
+    let mut current = effect(Stacking::Refresh, 1);
+    current.tick(60.0);
+    assert!(!current.reapply(&effect(Stacking::Refresh, 1)));
+    assert_eq!(current.stacks, 1);
+    assert_eq!(current.remaining, Some(100.0));
+  }
+
+  #[test]
+  fn stack_counts_applications_up_to_max() {
+    let mut current = effect(Stacking::Stack { max: 3 }, 1);
+    for _ in 0..5 {
+      assert!(!current.reapply(&effect(Stacking::Stack { max: 3 }, 1)));
+    }
+    assert_eq!(current.stacks, 3);
+  }
+
+  #[test]
+  fn strongest_wins_only_replaces_with_a_stronger_application() {
+    let mut current = effect(Stacking::StrongestWins, 1).with_strength(2.0);
+    assert!(!current.reapply(&effect(Stacking::StrongestWins, 1).with_strength(1.0)));
+    assert!(current.reapply(&effect(Stacking::StrongestWins, 1).with_strength(3.0)));
+  }
+
+  #[test]
+  fn per_caster_keys_instances_by_caster() {
+    let first = effect(Stacking::PerCaster, 1);
+    let second = effect(Stacking::PerCaster, 2);
+    assert_ne!(first.key(), second.key());
+    assert_eq!(effect(Stacking::Refresh, 1).key(), effect(Stacking::Refresh, 2).key());
+  }
+
+  #[test]
+  fn an_application_from_another_area_replaces_the_instance() {
+    let mut current = effect(Stacking::Refresh, 1);
+    current.caster_area = Some(("world".to_string(), 0));
+    let mut other = effect(Stacking::Refresh, 1);
+    other.caster_area = Some(("world".to_string(), 1));
+    assert!(current.reapply(&other));
   }
-  pub fn enable(&mut self, _: &mut Player) {}
-  pub fn disable(&self, _: &mut HeroWrapper) {}
-  pub fn update(&mut self) {}
 }
@@ -1,10 +1,13 @@
+use crate::config::RegionKind;
 use crate::proto::PackedEntity;
 use crate::resources::assets::hero::HeroWrapper;
+use crate::resources::obstacle::Obstacle;
 use crate::resources::utils::vector::Vector;
 use crate::resources::{distance, random, Boundary, EntityProps, EntityUpdateProps};
 use std::f32::consts::PI;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Entity {
   pub id: u64,
   pub type_id: u64,
@@ -97,12 +100,26 @@ impl Entity {
     }
   }
 
+  pub fn collide_obstacles(&mut self, obstacles: &[Obstacle]) -> bool {
+    let mut collided = false;
+    for obstacle in obstacles.iter() {
+      if let Some((offset, normal)) = obstacle.resolve(&self.pos, self.radius) {
+        self.pos.x += offset.x;
+        self.pos.y += offset.y;
+        let dot = self.vel.x * normal.x + self.vel.y * normal.y;
+        if dot < 0.0 {
+          self.vel.x -= 2.0 * dot * normal.x;
+          self.vel.y -= 2.0 * dot * normal.y;
+        }
+        collided = true;
+      }
+    }
+    collided
+  }
+
   pub fn interact(&mut self, hero: &mut HeroWrapper) {
     let player = hero.player_mut();
-    if !self.harmless
-      && player.pos.x > -player.radius
-      && player.pos.x - player.radius < self.boundary.w
-    {
+    if !self.harmless && !player.in_region(RegionKind::Safe) {
       if !player.immortal && !player.downed {
         if distance(player.pos.x - self.pos.x, player.pos.y - self.pos.y)
           <= self.radius + player.radius
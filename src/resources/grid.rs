@@ -0,0 +1,128 @@
+use crate::resources::player::PlayerView;
+use std::collections::HashMap;
+
+const CELL_SIZE: f64 = 4.0 * 32.0;
+
+pub struct SpatialGrid<T> {
+  cells: HashMap<(i64, i64), Vec<T>>,
+}
+
+impl<T: Copy + Ord> SpatialGrid<T> {
+  pub fn new() -> Self {
+    Self {
+      cells: HashMap::new(),
+    }
+  }
+
+  pub fn clear(&mut self) {
+    for cell in self.cells.values_mut() {
+      cell.clear();
+    }
+  }
+
+  pub fn insert(&mut self, item: T, x: f64, y: f64, radius: f64) {
+    for cx in cell(x - radius)..=cell(x + radius) {
+      for cy in cell(y - radius)..=cell(y + radius) {
+        self.cells.entry((cx, cy)).or_default().push(item);
+      }
+    }
+  }
+
+  // Returns every item whose cells overlap the square around the circle, in ascending order
+  pub fn query(&self, x: f64, y: f64, radius: f64) -> Vec<T> {
+    let mut result = Vec::new();
+    for cx in cell(x - radius)..=cell(x + radius) {
+      for cy in cell(y - radius)..=cell(y + radius) {
+        if let Some(items) = self.cells.get(&(cx, cy)) {
+          result.extend_from_slice(items);
+        }
+      }
+    }
+    result.sort_unstable();
+    result.dedup();
+    result
+  }
+}
+
+pub fn players_in_radius<'a>(
+  players: &'a [PlayerView],
+  grid: &SpatialGrid<usize>,
+  x: f64,
+  y: f64,
+  radius: f64,
+) -> Vec<&'a PlayerView> {
+  grid
+    .query(x, y, radius)
+    .into_iter()
+    .map(|index| &players[index])
+    .collect()
+}
+
+fn cell(value: f64) -> i64 {
+  (value / CELL_SIZE).floor() as i64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::resources::utils::vector::Vector;
+
+  fn view(id: i64, x: f64, y: f64, radius: f64) -> PlayerView {
+    PlayerView {
+      id,
+      pos: Vector::new(Some(x), Some(y)),
+      radius,
+      downed: false,
+      immortal: false,
+      regions: 0,
+    }
+  }
+
+  #[test]
+  fn query_returns_items_in_overlapping_cells_sorted_and_once() {
+    let mut grid = SpatialGrid::new();
+    grid.insert(3, 10.0, 10.0, 5.0);
+    // Sits on the corner of four cells, but is returned once
+    grid.insert(1, 2.0 * CELL_SIZE, 2.0 * CELL_SIZE, 10.0);
+    grid.insert(2, 10.0 * CELL_SIZE, 10.0 * CELL_SIZE, 5.0);
+
+    assert_eq!(grid.query(20.0, 20.0, 10.0), vec![3]);
+    assert_eq!(grid.query(2.0 * CELL_SIZE, 2.0 * CELL_SIZE, CELL_SIZE), vec![1]);
+    assert_eq!(grid.query(CELL_SIZE, CELL_SIZE, CELL_SIZE), vec![1, 3]);
+    assert!(grid.query(5.0 * CELL_SIZE, 5.0 * CELL_SIZE, 10.0).is_empty());
+  }
+
+  #[test]
+  fn query_handles_negative_coordinates() {
+    let mut grid = SpatialGrid::new();
+    grid.insert(1, -10.0, -10.0, 5.0);
+    grid.insert(2, 10.0, 10.0, 5.0);
+
+    assert_eq!(grid.query(-20.0, -20.0, 5.0), vec![1]);
+    assert_eq!(grid.query(0.0, 0.0, 1.0), vec![1, 2]);
+  }
+
+  #[test]
+  fn clear_empties_every_cell() {
+    let mut grid = SpatialGrid::new();
+    grid.insert(1, 10.0, 10.0, 5.0);
+    grid.clear();
+
+    assert!(grid.query(10.0, 10.0, 5.0).is_empty());
+  }
+
+  #[test]
+  fn players_in_radius_maps_indices_back_to_players() {
+    let players = vec![view(7, 10.0, 10.0, 15.0), view(8, 1000.0, 1000.0, 15.0)];
+    let mut grid = SpatialGrid::new();
+    for (index, player) in players.iter().enumerate() {
+      grid.insert(index, player.pos.x, player.pos.y, player.radius);
+    }
+
+    let found: Vec<i64> = players_in_radius(&players, &grid, 0.0, 0.0, 50.0)
+      .iter()
+      .map(|player| player.id)
+      .collect();
+    assert_eq!(found, vec![7]);
+  }
+}
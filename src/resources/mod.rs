@@ -2,26 +2,31 @@ use crate::bus::EventBus;
 use crate::resources::assets::entity::EntityWrapper;
 use crate::resources::assets::hero::HeroWrapper;
 use crate::resources::entity::Entity;
-use crate::resources::player::Player;
-use rand::rngs::ThreadRng;
-use rand::Rng;
+use crate::resources::grid::{players_in_radius, SpatialGrid};
+use crate::resources::player::{Player, PlayerView};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
 use std::cell::RefCell;
+use serde::{Deserialize, Serialize};
 
 pub mod area;
 pub mod assets;
 pub mod effect;
 pub mod entity;
+pub mod grid;
+pub mod obstacle;
 pub mod player;
+pub mod stats;
 pub mod utils;
 pub mod world;
 
 thread_local! {
-    static RNG: RefCell<ThreadRng> = RefCell::new(rand::rng());
+    static RNG: RefCell<ChaCha12Rng> = RefCell::new(ChaCha12Rng::from_os_rng());
 }
 
 // Structures
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Boundary {
   pub x: f64,
   pub y: f64,
@@ -39,40 +44,54 @@ pub struct EntityProps {
 }
 
 pub struct EntityUpdateProps<'a> {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
-  pub players: Vec<&'a Player>,
+  pub players: &'a [PlayerView],
+  pub grid: &'a SpatialGrid<usize>,
   pub event_bus: &'a mut EventBus,
 }
 
+impl<'a> EntityUpdateProps<'a> {
+  pub fn players_in_radius(&self, x: f64, y: f64, radius: f64) -> Vec<&'a PlayerView> {
+    players_in_radius(&self.players, self.grid, x, y, radius)
+  }
+}
+
 pub struct EffectUpdateProps<'a> {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
-  pub caster: &'a EntityWrapper,
+  pub caster: Option<&'a EntityWrapper>,
   pub target: &'a mut HeroWrapper,
   pub boundary: Boundary,
 }
 
 pub struct PartEffectUpdateProps<'a> {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
   pub target: &'a Player,
 }
 
 pub struct UpdateProps {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
 }
 
 pub struct PlayerUpdateProps<'a> {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
-  pub players: Vec<&'a Player>,
+  pub players: &'a [PlayerView],
+  pub grid: &'a SpatialGrid<usize>,
   pub event_bus: &'a mut EventBus,
 }
 
+impl<'a> PlayerUpdateProps<'a> {
+  pub fn players_in_radius(&self, x: f64, y: f64, radius: f64) -> Vec<&'a PlayerView> {
+    players_in_radius(&self.players, self.grid, x, y, radius)
+  }
+}
+
 pub struct EffectProps<'a> {
-  pub delta: i64,
+  pub delta: f64,
   pub time_fix: f64,
   pub target: &'a mut Player,
   pub caster: &'a mut Entity,
@@ -99,3 +118,35 @@ pub fn random(min: f64, max: f64) -> f64 {
     r * (max - min) + min
   })
 }
+
+pub fn seed_random(seed: u64) {
+  RNG.with(|rng| {
+    *rng.borrow_mut() = ChaCha12Rng::seed_from_u64(seed);
+  })
+}
+
+// The full generator state, so a snapshot continues the exact same sequence
+pub fn random_state() -> ChaCha12Rng {
+  RNG.with(|rng| rng.borrow().clone())
+}
+
+pub fn set_random_state(state: ChaCha12Rng) {
+  RNG.with(|rng| {
+    *rng.borrow_mut() = state;
+  })
+}
+
+pub fn swap_random(other: &mut ChaCha12Rng) {
+  RNG.with(|rng| {
+    std::mem::swap(&mut *rng.borrow_mut(), other);
+  })
+}
+
+pub fn stream_seed(seed: u64, name: &str, index: u64) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325 ^ seed;
+  for byte in name.bytes().chain(index.to_le_bytes()) {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
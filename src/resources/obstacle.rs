@@ -0,0 +1,200 @@
+use crate::config::RawObstacle;
+use crate::proto::PackedObstacle;
+use crate::resources::utils::vector::Vector;
+use crate::resources::distance;
+
+#[derive(Clone, Debug)]
+pub struct Obstacle {
+  points: Vec<Vector>,
+  min: Vector,
+  max: Vector,
+  // Sign of the signed area, tells which side of an edge is outside
+  winding: f64,
+}
+
+impl Obstacle {
+  pub fn new(raw_obstacle: &RawObstacle) -> Self {
+    let points: Vec<Vector> = match raw_obstacle {
+      RawObstacle::Rect { x, y, w, h } => vec![
+        Vector::new(Some(*x), Some(*y)),
+        Vector::new(Some(x + w), Some(*y)),
+        Vector::new(Some(x + w), Some(y + h)),
+        Vector::new(Some(*x), Some(y + h)),
+      ],
+      RawObstacle::Polygon { points } => points
+        .iter()
+        .map(|point| Vector::new(Some(point[0]), Some(point[1])))
+        .collect(),
+    };
+
+    let mut min = Vector::new(Some(f64::MAX), Some(f64::MAX));
+    let mut max = Vector::new(Some(f64::MIN), Some(f64::MIN));
+    for point in points.iter() {
+      min.x = min.x.min(point.x);
+      min.y = min.y.min(point.y);
+      max.x = max.x.max(point.x);
+      max.y = max.y.max(point.y);
+    }
+
+    let mut area = 0.0;
+    let mut j = points.len().saturating_sub(1);
+    for i in 0..points.len() {
+      area += points[j].x * points[i].y - points[i].x * points[j].y;
+      j = i;
+    }
+    let winding = if area < 0.0 { -1.0 } else { 1.0 };
+
+    Self {
+      points,
+      min,
+      max,
+      winding,
+    }
+  }
+
+  pub fn contains(&self, x: f64, y: f64) -> bool {
+    if self.points.len() < 3 {
+      return false;
+    }
+    let mut inside = false;
+    let mut j = self.points.len() - 1;
+    for i in 0..self.points.len() {
+      let a = &self.points[i];
+      let b = &self.points[j];
+      if (a.y > y) != (b.y > y) && x < (b.x - a.x) * (y - a.y) / (b.y - a.y) + a.x {
+        inside = !inside;
+      }
+      j = i;
+    }
+    inside
+  }
+
+  // Returns the offset that moves the circle out of the obstacle and the surface normal
+  pub fn resolve(&self, pos: &Vector, radius: f64) -> Option<(Vector, Vector)> {
+    if self.points.len() < 3
+      || pos.x + radius < self.min.x
+      || pos.x - radius > self.max.x
+      || pos.y + radius < self.min.y
+      || pos.y - radius > self.max.y
+    {
+      return None;
+    }
+
+    let mut closest = Vector::new(None, None);
+    let mut closest_dist = f64::MAX;
+    let mut edge_normal = (0.0, 0.0);
+    let mut j = self.points.len() - 1;
+    for i in 0..self.points.len() {
+      let a = &self.points[j];
+      let b = &self.points[i];
+      let (ex, ey) = (b.x - a.x, b.y - a.y);
+      let length = ex * ex + ey * ey;
+      let t = if length > 0.0 {
+        (((pos.x - a.x) * ex + (pos.y - a.y) * ey) / length).clamp(0.0, 1.0)
+      } else {
+        0.0
+      };
+      let point = Vector::new(Some(a.x + ex * t), Some(a.y + ey * t));
+      let dist = distance(pos.x - point.x, pos.y - point.y);
+      if dist < closest_dist {
+        closest_dist = dist;
+        closest = point;
+        if length > 0.0 {
+          let length = length.sqrt();
+          edge_normal = (self.winding * ey / length, -self.winding * ex / length);
+        }
+      }
+      j = i;
+    }
+
+    // Exactly on the boundary there is no direction to the closest point, the edge decides
+    if closest_dist <= f64::EPSILON {
+      let (nx, ny) = edge_normal;
+      if nx == 0.0 && ny == 0.0 {
+        return None;
+      }
+      return Some((
+        Vector::new(Some(nx * radius), Some(ny * radius)),
+        Vector::new(Some(nx), Some(ny)),
+      ));
+    }
+
+    let inside = self.contains(pos.x, pos.y);
+    if !inside && closest_dist >= radius {
+      return None;
+    }
+
+    let (mut nx, mut ny) = (
+      (pos.x - closest.x) / closest_dist,
+      (pos.y - closest.y) / closest_dist,
+    );
+    let mut depth = radius - closest_dist;
+    if inside {
+      nx = -nx;
+      ny = -ny;
+      depth = radius + closest_dist;
+    }
+
+    Some((
+      Vector::new(Some(nx * depth), Some(ny * depth)),
+      Vector::new(Some(nx), Some(ny)),
+    ))
+  }
+
+  pub fn pack(&self) -> PackedObstacle {
+    let mut points = Vec::with_capacity(self.points.len() * 2);
+    for point in self.points.iter() {
+      points.push(point.x as f32);
+      points.push(point.y as f32);
+    }
+    PackedObstacle { points }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn square() -> Obstacle {
+    Obstacle::new(&RawObstacle::Rect {
+      x: 0.0,
+      y: 0.0,
+      w: 100.0,
+      h: 100.0,
+    })
+  }
+
+  #[test]
+  fn circle_overlapping_an_edge_is_pushed_out() {
+    let pos = Vector::new(Some(50.0), Some(-5.0));
+    let (offset, normal) = square().resolve(&pos, 10.0).unwrap();
+    assert_eq!((offset.x, offset.y), (0.0, -5.0));
+    assert_eq!((normal.x, normal.y), (0.0, -1.0));
+  }
+
+  #[test]
+  fn circle_centered_on_an_edge_is_pushed_out_along_its_normal() {
+    let obstacle = square();
+    for (x, y, nx, ny) in [
+      (50.0, 0.0, 0.0, -1.0),
+      (100.0, 50.0, 1.0, 0.0),
+      (50.0, 100.0, 0.0, 1.0),
+      (0.0, 50.0, -1.0, 0.0),
+    ] {
+      let pos = Vector::new(Some(x), Some(y));
+      let (offset, normal) = obstacle.resolve(&pos, 10.0).unwrap();
+      assert_eq!((normal.x, normal.y), (nx, ny));
+      assert_eq!((offset.x, offset.y), (nx * 10.0, ny * 10.0));
+    }
+  }
+
+  #[test]
+  fn winding_does_not_change_the_normal() {
+    let obstacle = Obstacle::new(&RawObstacle::Polygon {
+      points: vec![[0.0, 0.0], [0.0, 100.0], [100.0, 100.0], [100.0, 0.0]],
+    });
+    let pos = Vector::new(Some(50.0), Some(0.0));
+    let (_, normal) = obstacle.resolve(&pos, 10.0).unwrap();
+    assert_eq!((normal.x, normal.y), (0.0, -1.0));
+  }
+}
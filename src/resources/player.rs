@@ -1,11 +1,15 @@
+use crate::config::RegionKind;
 use crate::proto::PackedPlayer;
+use crate::resources::obstacle::Obstacle;
+use crate::resources::stats::{BaseStats, Modifier, Stat};
 use crate::resources::utils::input::Input;
 use crate::resources::utils::join::JoinProps;
 use crate::resources::utils::vector::Vector;
 use crate::resources::{distance, Boundary, PlayerUpdateProps};
 use crate::CONFIG;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Player {
   pub name: String,
   pub id: i64,
@@ -15,6 +19,8 @@ pub struct Player {
   acc: Vector,
   slide: Vector,
   pub speed: f64,
+  pub base: BaseStats,
+  pub modifiers: Vec<Modifier>,
   pub energy: f64,
   pub max_energy: f64,
   pub downed: bool,
@@ -29,6 +35,29 @@ pub struct Player {
   pub state_meta: f64,
   pub to_delete: bool,
   pub hero: u32,
+  #[serde(default)]
+  pub regions: u32,
+  #[serde(default)]
+  pub abilities_disabled: bool,
+  // Set by a warp until the player touches no portal, so arriving inside one never warps back
+  #[serde(default)]
+  pub in_portal: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerView {
+  pub id: i64,
+  pub pos: Vector,
+  pub radius: f64,
+  pub downed: bool,
+  pub immortal: bool,
+  pub regions: u32,
+}
+
+impl PlayerView {
+  pub fn in_region(&self, kind: RegionKind) -> bool {
+    self.regions & kind.flag() != 0
+  }
 }
 
 impl Player {
@@ -43,6 +72,14 @@ impl Player {
       acc: Vector::new(None, None),
       slide: Vector::new(None, None),
       speed: spawn.speed,
+      base: BaseStats {
+        speed: spawn.speed,
+        max_speed: spawn.max_speed,
+        radius: spawn.radius,
+        regeneration: spawn.regeneration,
+        max_energy: spawn.max_energy,
+      },
+      modifiers: Vec::new(),
       energy: spawn.energy,
       max_energy: spawn.max_energy,
       downed: false,
@@ -56,6 +93,9 @@ impl Player {
       area: spawn.area as u64,
       to_delete: false,
       hero: 0,
+      regions: 0,
+      abilities_disabled: false,
+      in_portal: false,
     }
   }
 
@@ -95,7 +135,7 @@ impl Player {
 
     self.regenerate_energy(props.delta);
     if self.downed {
-      self.death_timer -= props.delta as f64 / 1000.0;
+      self.death_timer -= props.delta / 1000.0;
       if self.death_timer < 0.0 {
         self.to_delete = true;
       }
@@ -107,8 +147,8 @@ impl Player {
     // self.effects.clear();
   }
 
-  fn regenerate_energy(&mut self, delta: i64) {
-    self.energy += self.regeneration * (delta as f64 / 1000.0);
+  fn regenerate_energy(&mut self, delta: f64) {
+    self.energy += self.regeneration * (delta / 1000.0);
     if self.energy > self.max_energy {
       self.energy = self.max_energy;
     }
@@ -153,6 +193,34 @@ impl Player {
     }
   }
 
+  pub fn in_region(&self, kind: RegionKind) -> bool {
+    self.regions & kind.flag() != 0
+  }
+
+  pub fn can_use_abilities(&self) -> bool {
+    !self.abilities_disabled && !self.in_region(RegionKind::NoAbility)
+  }
+
+  // Derived stats are always rebuilt from the base stats and the active modifiers
+  pub fn recompute_stats(&mut self) {
+    self.speed = self.base.resolve(Stat::Speed, &self.modifiers);
+    self.radius = self.base.resolve(Stat::Radius, &self.modifiers);
+    self.regeneration = self.base.resolve(Stat::Regeneration, &self.modifiers);
+    self.max_energy = self.base.resolve(Stat::MaxEnergy, &self.modifiers);
+    self.energy = self.energy.min(self.max_energy);
+  }
+
+  pub fn view(&self) -> PlayerView {
+    PlayerView {
+      id: self.id,
+      pos: self.pos.clone(),
+      radius: self.radius,
+      downed: self.downed,
+      immortal: self.immortal,
+      regions: self.regions,
+    }
+  }
+
   pub fn knock(&mut self) {
     self.downed = true;
     self.death_timer = 60.0;
@@ -162,7 +230,14 @@ impl Player {
     self.downed = false;
   }
 
-  pub fn collide(&mut self, boundary: Boundary) {
+  pub fn collide(&mut self, boundary: Boundary, obstacles: &[Obstacle]) {
+    for obstacle in obstacles.iter() {
+      if let Some((offset, _)) = obstacle.resolve(&self.pos, self.radius) {
+        self.pos.x += offset.x;
+        self.pos.y += offset.y;
+      }
+    }
+
     if self.pos.x - self.radius < boundary.x {
       self.pos.x = boundary.x + self.radius;
     }
@@ -194,6 +269,8 @@ impl Player {
       died: self.downed,
       state_meta: (self.state_meta * 2.0).round().abs() as u32,
       hero: self.hero,
+      abilities: Vec::new(),
+      modifiers: self.modifiers.iter().map(|modifier| modifier.pack()).collect(),
     }
   }
 }
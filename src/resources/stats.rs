@@ -0,0 +1,161 @@
+use crate::proto::PackedModifier;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stat {
+  Speed,
+  Radius,
+  Regeneration,
+  MaxEnergy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+  Add,
+  Multiply,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Source {
+  Effect(u64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Modifier {
+  pub stat: Stat,
+  pub operation: Operation,
+  pub value: f64,
+  pub source: Source,
+}
+
+impl Modifier {
+  pub fn new(stat: Stat, operation: Operation, value: f64, source: Source) -> Self {
+    Self {
+      stat,
+      operation,
+      value,
+      source,
+    }
+  }
+
+  pub fn pack(&self) -> PackedModifier {
+    let (source_type, source_id) = match self.source {
+      Source::Effect(id) => (0, id),
+    };
+    PackedModifier {
+      stat: self.stat as u32,
+      operation: self.operation as u32,
+      value: self.value as f32,
+      source_type,
+      source_id,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BaseStats {
+  pub speed: f64,
+  pub max_speed: f64,
+  pub radius: f64,
+  pub regeneration: f64,
+  pub max_energy: f64,
+}
+
+impl BaseStats {
+  fn get(&self, stat: Stat) -> f64 {
+    match stat {
+      Stat::Speed => self.speed,
+      Stat::Radius => self.radius,
+      Stat::Regeneration => self.regeneration,
+      Stat::MaxEnergy => self.max_energy,
+    }
+  }
+
+  // Additive modifiers apply before multiplicative ones, each group in list order.
+  // Only the base speed is capped at max_speed, effects still speed a capped player up
+  pub fn resolve(&self, stat: Stat, modifiers: &[Modifier]) -> f64 {
+    let mut value = self.get(stat);
+    if stat == Stat::Speed {
+      value = value.min(self.max_speed);
+    }
+    for modifier in modifiers.iter() {
+      if modifier.stat == stat && modifier.operation == Operation::Add {
+        value += modifier.value;
+      }
+    }
+    for modifier in modifiers.iter() {
+      if modifier.stat == stat && modifier.operation == Operation::Multiply {
+        value *= modifier.value;
+      }
+    }
+    value.max(0.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+
+  fn spawn_stats() -> BaseStats {
+    let spawn = Config::new().spawn;
+    BaseStats {
+      speed: spawn.speed,
+      max_speed: spawn.max_speed,
+      radius: spawn.radius,
+      regeneration: spawn.regeneration,
+      max_energy: spawn.max_energy,
+    }
+  }
+
+  fn modifier(stat: Stat, operation: Operation, value: f64) -> Modifier {
+    Modifier::new(stat, operation, value, Source::Effect(0))
+  }
+
+  #[test]
+  fn additive_modifiers_apply_before_multiplicative_ones() {
+    let stats = spawn_stats();
+    let modifiers = [
+      modifier(Stat::Radius, Operation::Multiply, 2.0),
+      modifier(Stat::Radius, Operation::Add, 5.0),
+    ];
+    assert_eq!(stats.resolve(Stat::Radius, &modifiers), (stats.radius + 5.0) * 2.0);
+  }
+
+  #[test]
+  fn modifiers_only_touch_their_own_stat() {
+    let stats = spawn_stats();
+    let modifiers = [modifier(Stat::Radius, Operation::Multiply, 0.0)];
+    assert_eq!(stats.resolve(Stat::Speed, &modifiers), stats.speed);
+  }
+
+  #[test]
+  fn base_speed_is_capped_at_max_speed() {
+    let mut stats = spawn_stats();
+    stats.speed = stats.max_speed + 10.0;
+    assert_eq!(stats.resolve(Stat::Speed, &[]), stats.max_speed);
+  }
+
+  #[test]
+  fn slipped_speeds_up_a_spawn_default_player() {
+    let stats = spawn_stats();
+    // Slipped adds the target's base speed once
+    let modifiers = [modifier(Stat::Speed, Operation::Add, stats.speed)];
+    assert_eq!(stats.resolve(Stat::Speed, &modifiers), stats.speed * 2.0);
+  }
+
+  #[test]
+  fn effects_speed_up_a_player_at_max_speed() {
+    let mut stats = spawn_stats();
+    stats.speed = stats.max_speed;
+    let modifiers = [modifier(Stat::Speed, Operation::Multiply, 1.5)];
+    assert_eq!(stats.resolve(Stat::Speed, &modifiers), stats.max_speed * 1.5);
+  }
+
+  #[test]
+  fn resolved_stats_never_go_negative() {
+    let stats = spawn_stats();
+    let modifiers = [modifier(Stat::Speed, Operation::Add, -100.0)];
+    assert_eq!(stats.resolve(Stat::Speed, &modifiers), 0.0);
+  }
+}
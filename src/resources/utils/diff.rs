@@ -1,4 +1,7 @@
-use crate::proto::{PackedEntity, PackedPlayer, PartialEntity, PartialPlayer};
+use crate::proto::{
+  PackedAbilities, PackedEffect, PackedEntity, PackedModifiers, PackedPlayer, PartialEntity,
+  PartialPlayer,
+};
 
 #[macro_export]
 macro_rules! diff_field {
@@ -19,6 +22,17 @@ macro_rules! diff_field {
   }};
 }
 
+#[macro_export]
+macro_rules! merge_field {
+  ($self:ident, $new:ident, $($field:ident),+) => {{
+    $(
+      if $new.$field.is_some() {
+        $self.$field = $new.$field.clone();
+      }
+    )+
+  }};
+}
+
 #[macro_export]
 macro_rules! diff_delta {
   ($self:ident, $new:ident, $field:ident) => {{
@@ -88,6 +102,8 @@ impl PackedPlayer {
       || self.world != new.world
       || self.area != new.area
       || self.state_meta != new.state_meta
+      || self.abilities != new.abilities
+      || self.modifiers != new.modifiers
     {
       changed = true;
     }
@@ -106,8 +122,54 @@ impl PackedPlayer {
         area: diff_field!(self, new, area),
         world: (self.world != new.world).then(|| new.world.clone()),
         died: (self.died != new.died).then(|| new.died),
+        abilities: (self.abilities != new.abilities).then(|| PackedAbilities {
+          items: new.abilities.clone(),
+        }),
+        modifiers: (self.modifiers != new.modifiers).then(|| PackedModifiers {
+          items: new.modifiers.clone(),
+        }),
       },
       changed,
     )
   }
 }
+
+impl PackedEffect {
+  // The remaining time runs down every tick, clients count it down on their own
+  pub fn changed(&self, new: &PackedEffect) -> bool {
+    self.duration != new.duration
+      || self.stacks != new.stacks
+      || self.caster_id != new.caster_id
+      || self.caster_type != new.caster_type
+  }
+}
+
+impl PartialEntity {
+  // Applies a later diff on top of this one, later fields win
+  pub fn merge(&mut self, new: &PartialEntity) {
+    merge_field!(self, new, x, y, radius, harmless, state, state_metadata, alpha);
+  }
+}
+
+impl PartialPlayer {
+  pub fn merge(&mut self, new: &PartialPlayer) {
+    merge_field!(
+      self,
+      new,
+      x,
+      y,
+      radius,
+      speed,
+      energy,
+      max_energy,
+      death_timer,
+      state,
+      state_meta,
+      area,
+      world,
+      died,
+      abilities,
+      modifiers
+    );
+  }
+}
@@ -13,6 +13,7 @@ pub struct Input {
   pub mouse_pos_y: f64,
   pub first_ability: bool,
   pub second_ability: bool,
+  pub sequence: u32,
 }
 
 #[napi]
@@ -30,6 +31,7 @@ impl Input {
       mouse_pos_y: 0.0,
       first_ability: false,
       second_ability: false,
+      sequence: 0,
     }
   }
   #[napi]
@@ -81,4 +83,9 @@ impl Input {
   pub fn set_second_ability(&mut self, val: bool) {
     self.second_ability = val;
   }
+
+  #[napi]
+  pub fn set_sequence(&mut self, val: u32) {
+    self.sequence = val;
+  }
 }
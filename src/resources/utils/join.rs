@@ -5,12 +5,34 @@ use napi_derive::napi;
 pub struct JoinProps {
   pub name: String,
   pub id: i64,
+  pub hero: Option<String>,
+  pub compression: Option<String>,
+  pub view_radius: Option<f64>,
 }
 
 #[napi]
 impl JoinProps {
   #[napi(constructor)]
-  pub fn new(name: String, id: i64) -> JoinProps {
-    JoinProps { name, id }
+  pub fn new(
+    name: String,
+    id: i64,
+    hero: Option<String>,
+    compression: Option<String>,
+    view_radius: Option<f64>,
+  ) -> JoinProps {
+    JoinProps {
+      name,
+      id,
+      hero,
+      compression,
+      view_radius,
+    }
+  }
+
+  pub fn hero_name(&self) -> &str {
+    match &self.hero {
+      Some(hero) => hero.as_str(),
+      None => "maven",
+    }
   }
 }
@@ -1,7 +1,8 @@
 use crate::resources::random;
 use napi_derive::napi;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[napi]
 pub struct Vector {
   pub x: f64,
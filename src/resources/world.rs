@@ -2,6 +2,7 @@ use crate::config::RawWorld;
 use crate::proto::PackedArea;
 use crate::resources::area::Area;
 use crate::resources::player::Player;
+use crate::resources::stream_seed;
 
 pub struct World {
   raw_world: RawWorld,
@@ -9,10 +10,11 @@ pub struct World {
 }
 
 impl World {
-  pub fn new(raw_world: RawWorld) -> Self {
+  pub fn new(raw_world: RawWorld, seed: u64) -> Self {
     let mut areas = Vec::new();
-    for a in &raw_world.areas {
-      areas.push(Area::new(a.clone()));
+    for (index, a) in raw_world.areas.iter().enumerate() {
+      let area_seed = stream_seed(seed, &raw_world.name, index as u64);
+      areas.push(Area::new(a.clone(), area_seed));
     }
     Self { raw_world, areas }
   }
@@ -37,6 +39,7 @@ impl World {
       area: area_id as u64,
       world: self.raw_world.name.clone(),
       entities: area.get_packed_entities(),
+      obstacles: area.obstacles.iter().map(|obstacle| obstacle.pack()).collect(),
     }
   }
 }
@@ -0,0 +1,193 @@
+use crate::bus::{EventBus, NetworkBus, PlayerEvent};
+use crate::managers::player::PlayersManager;
+use crate::persist;
+use crate::proto::package::Kind;
+use crate::resources::assets::effect::PlayerEffectWrapper;
+use crate::resources::assets::entity::EntityWrapper;
+use crate::resources::assets::hero::HeroWrapper;
+use crate::resources::world::World;
+use crate::resources::{random_state, set_random_state};
+use napi::{Error, Status};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+  pub seed: u64,
+  pub tick: u64,
+  // Generator states are stored whole, so a restored engine draws the exact same numbers
+  pub rng: ChaCha12Rng,
+  pub worlds: Vec<WorldSnapshot>,
+  pub players: Vec<HeroWrapper>,
+  pub effects: Vec<PlayerEffectWrapper>,
+  pub entities_to_spawn: Vec<EntityWrapper>,
+  pub players_events: Vec<PlayerEvent>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorldSnapshot {
+  pub name: String,
+  pub areas: Vec<AreaSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AreaSnapshot {
+  pub entities: BTreeMap<u64, EntityWrapper>,
+  pub players_id: Vec<i64>,
+  pub next_id: u64,
+  pub rng: ChaCha12Rng,
+}
+
+impl Snapshot {
+  pub fn capture(
+    seed: u64,
+    tick: u64,
+    worlds: &HashMap<String, World>,
+    players_manager: &PlayersManager,
+    event_bus: &EventBus,
+  ) -> Self {
+    let mut packed_worlds = Vec::new();
+    for (name, world) in worlds.iter() {
+      let mut areas = Vec::new();
+      for area in world.areas.iter() {
+        areas.push(AreaSnapshot {
+          entities: area.entities.clone(),
+          players_id: area.players_id.clone(),
+          next_id: area.next_id,
+          rng: area.rng().clone(),
+        });
+      }
+      packed_worlds.push(WorldSnapshot {
+        name: name.clone(),
+        areas,
+      });
+    }
+
+    let mut effects = Vec::new();
+    for (_, player_effects) in players_manager.effects.iter() {
+      for (_, effect) in player_effects.iter() {
+        effects.push(effect.clone());
+      }
+    }
+
+    Self {
+      seed,
+      tick,
+      rng: random_state(),
+      worlds: packed_worlds,
+      players: players_manager.players.values().cloned().collect(),
+      effects,
+      entities_to_spawn: event_bus.entities_to_spawn.clone(),
+      players_events: event_bus.players_events.clone(),
+    }
+  }
+
+  pub fn encode(&self) -> Result<Vec<u8>, Error> {
+    persist::encode(self, "snapshot")
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+    persist::decode(bytes, "snapshot")
+  }
+
+  // Every world and area is checked before anything is touched, so a rejected snapshot leaves
+  // the engine as it was
+  pub fn validate(&self, worlds: &HashMap<String, World>) -> Result<(), Error> {
+    for world_snapshot in self.worlds.iter() {
+      let world = match worlds.get(&world_snapshot.name) {
+        Some(world) => world,
+        None => {
+          return Err(Error::new(
+            Status::InvalidArg,
+            format!("World not found: {}", world_snapshot.name),
+          ));
+        }
+      };
+      if world.areas.len() != world_snapshot.areas.len() {
+        return Err(Error::new(
+          Status::InvalidArg,
+          format!(
+            "World {} has {} areas, the snapshot has {}",
+            world_snapshot.name,
+            world.areas.len(),
+            world_snapshot.areas.len()
+          ),
+        ));
+      }
+    }
+    Ok(())
+  }
+
+  pub fn apply(
+    self,
+    worlds: &mut HashMap<String, World>,
+    players_manager: &mut PlayersManager,
+    event_bus: &mut EventBus,
+  ) -> Result<(), Error> {
+    self.validate(worlds)?;
+
+    set_random_state(self.rng);
+
+    for world_snapshot in self.worlds {
+      if let Some(world) = worlds.get_mut(&world_snapshot.name) {
+        for (area, area_snapshot) in world.areas.iter_mut().zip(world_snapshot.areas) {
+          area.restore(
+            area_snapshot.entities,
+            area_snapshot.players_id,
+            area_snapshot.next_id,
+            area_snapshot.rng,
+          );
+        }
+      }
+    }
+
+    players_manager.players.clear();
+    players_manager.effects.clear();
+    for hero in self.players {
+      players_manager.players.insert(hero.player().id, hero);
+    }
+    for effect in self.effects {
+      players_manager
+        .effects
+        .entry(effect.effect().target_id)
+        .or_default()
+        .insert(effect.effect().key(), effect);
+    }
+
+    event_bus.entities_to_spawn = self.entities_to_spawn;
+    event_bus.players_events = self.players_events;
+
+    Ok(())
+  }
+}
+
+// Connected clients keep what they negotiated at join and get the restored state in full,
+// anything queued for them still described the replaced state. Returns the clients that have
+// no player anymore, they are disconnected
+pub fn resync_clients(players_manager: &PlayersManager, network_bus: &mut NetworkBus) -> Vec<i64> {
+  let mut dropped: Vec<i64> = network_bus
+    .direct_clients
+    .keys()
+    .filter(|id| !players_manager.players.contains_key(id))
+    .copied()
+    .collect();
+  dropped.sort();
+  for id in dropped.iter() {
+    network_bus.remove_client(*id);
+  }
+  for packages in network_bus.area_clients.values_mut() {
+    packages.items.clear();
+  }
+  let roster = players_manager.roster();
+  for (id, hero) in players_manager.players.iter() {
+    match network_bus.direct_clients.get_mut(id) {
+      Some(client) => client.clear_packages(),
+      None => network_bus.add_client(*id, None, None),
+    }
+    network_bus.resync(*id);
+    network_bus.add_direct_package(*id, Kind::Myself(hero.pack()));
+    network_bus.add_direct_package(*id, Kind::Roster(roster.clone()));
+  }
+  dropped
+}
@@ -151,6 +151,7 @@ const tick = () => {
   for (const index in clientsInput) {
     engine.input(Number(index), clientsInput[index])
     clientsInput[index].setFirstAbility(false)
+    clientsInput[index].setSecondAbility(false)
   }
   // console.time('Compute Engine')
   const packages = engine.update() as Record<string, Buffer>